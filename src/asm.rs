@@ -0,0 +1,5 @@
+mod assembler;
+mod disassembler;
+
+pub use assembler::{assemble, AsmError, CompileError, LexError, LineMap, ParseError};
+pub use disassembler::disassemble;
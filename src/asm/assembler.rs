@@ -39,6 +39,9 @@ enum Instr {
     LoadReg(u8),
     JumpLabel(String),
     JumpRegLabel(String),
+    CallLabel(String),
+    SetILabel(String),
+    Bytes(Vec<u8>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -74,6 +77,7 @@ enum Token {
     StoreBCD,
     Store,
     Load,
+    Byte,
     Register(u8),
     Value(u16),
     Label(String),
@@ -83,24 +87,28 @@ enum Token {
     EndLine,
 }
 
+// address of every instruction paired with the source line it came from
+pub type LineMap = Vec<(u16, usize)>;
+
 #[derive(Debug)]
 struct Prog {
     instructions: Vec<Instr>,
     label_map: Vec<(String, u16)>,
+    line_map: LineMap,
 }
 
-fn parse_hex(input: &str) -> Result<u16, LexError> {
+fn parse_hex(input: &str) -> u16 {
     let mut num = 0;
     for c in input.chars() {
-        if c >= '0' && c <= '9' {
+        if c.is_ascii_digit() {
             num = (num << 4) | (c as u16 - '0' as u16);
-        } else if c >= 'a' && c <= 'f' {
+        } else if ('a'..='f').contains(&c) {
             num = (num << 4) | (c as u16 - 'a' as u16 + 10);
-        } else if c >= 'A' && c <= 'F' {
+        } else if ('A'..='F').contains(&c) {
             num = (num << 4) | (c as u16 - 'A' as u16 + 10);
         }
     }
-    Ok(num)
+    num
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -121,7 +129,30 @@ pub enum CompileError {
     ParseError(ParseError),
 }
 
-fn lex(source: &str) -> Result<Vec<Token>, LexError> {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AsmError {
+    pub error: CompileError,
+    // counted from 1
+    pub line: usize,
+}
+
+// the line the unlexed rest of source starts on
+fn lex_error(source: &str, rest: &str, error: LexError) -> AsmError {
+    let done = &source[..source.len() - rest.len()];
+    AsmError {
+        error: CompileError::LexError(error),
+        line: done.matches('\n').count() + 1,
+    }
+}
+
+fn parse_error(line: usize, error: ParseError) -> AsmError {
+    AsmError {
+        error: CompileError::ParseError(error),
+        line,
+    }
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, AsmError> {
     let r_sys = Regex::new(r"^sys\s").unwrap();
     let r_clr = Regex::new(r"^clr\s").unwrap();
     let r_ret = Regex::new(r"^ret\s").unwrap();
@@ -153,6 +184,7 @@ fn lex(source: &str) -> Result<Vec<Token>, LexError> {
     let r_sbcd = Regex::new(r"^sbcd\s").unwrap();
     let r_sb = Regex::new(r"^sb\s").unwrap();
     let r_lb = Regex::new(r"^lb\s").unwrap();
+    let r_byte = Regex::new(r"^\.byte\s").unwrap();
     let r_reg = Regex::new(r"^(V|v)((?<dec>[0-9]{2})|(?<hex>[0-9a-fA-F]))").unwrap();
     let r_val = Regex::new(r"^(?<hex>0x[0-9a-fA-F]+)|^(?<dec>[0-9]+)").unwrap();
     let r_comment = Regex::new(r"^#.?").unwrap();
@@ -174,24 +206,24 @@ fn lex(source: &str) -> Result<Vec<Token>, LexError> {
                 match dec.as_str().parse::<u16>() {
                     Ok(num) => {
                         if num & 0xF000 != 0x0000 {
-                            return Err(LexError::NumberTooWide);
+                            return Err(lex_error(source, line, LexError::NumberTooWide));
                         }
                         val = num;
                     }
-                    Err(_) => return Err(LexError::NumberTooWide),
+                    Err(_) => return Err(lex_error(source, line, LexError::NumberTooWide)),
                 }
                 tokens.push(Token::Value(val));
                 line = &line[dec.len()..];
             } else if let Some(hex) = caps.name("hex") {
-                val = parse_hex(&hex.as_str()[2..])?;
+                val = parse_hex(&hex.as_str()[2..]);
                 if val & 0xF000 != 0x0000 {
-                    return Err(LexError::NumberTooWide);
+                    return Err(lex_error(source, line, LexError::NumberTooWide));
                 }
 
                 tokens.push(Token::Value(val));
                 line = &line[hex.len()..];
             } else {
-                return Err(LexError::IllegalToken);
+                return Err(lex_error(source, line, LexError::IllegalToken));
             }
         } else if r_reg.is_match(line) {
             let caps = r_reg.captures(line).unwrap();
@@ -200,72 +232,72 @@ fn lex(source: &str) -> Result<Vec<Token>, LexError> {
                 match dec.as_str().parse::<u8>() {
                     Ok(num) => {
                         if num & 0xF0 != 0x00 {
-                            return Err(LexError::NumberTooWide);
+                            return Err(lex_error(source, line, LexError::NumberTooWide));
                         }
                         val = num;
                     }
-                    Err(_) => return Err(LexError::NumberTooWide),
+                    Err(_) => return Err(lex_error(source, line, LexError::NumberTooWide)),
                 }
                 tokens.push(Token::Register(val));
                 line = &line[(dec.len() + 1)..];
             } else if let Some(hex) = caps.name("hex") {
-                val = parse_hex(&hex.as_str())? as u8;
+                val = parse_hex(hex.as_str()) as u8;
                 tokens.push(Token::Register(val));
                 line = &line[(hex.len() + 1)..];
             } else {
-                return Err(LexError::IllegalToken);
+                return Err(lex_error(source, line, LexError::IllegalToken));
             }
         } else if r_rand.is_match(line) {
             tokens.push(Token::Rand);
-            line = &line[5..];
+            line = &line[4..];
         } else if r_draw.is_match(line) {
             tokens.push(Token::Draw);
-            line = &line[5..];
+            line = &line[4..];
         } else if r_sbcd.is_match(line) {
             tokens.push(Token::StoreBCD);
-            line = &line[5..];
+            line = &line[4..];
         } else if r_call.is_match(line) {
             tokens.push(Token::Call);
-            line = &line[5..];
+            line = &line[4..];
         } else if r_subn.is_match(line) {
             tokens.push(Token::SubRegNeg);
-            line = &line[5..];
+            line = &line[4..];
         } else if r_sys.is_match(line) {
             tokens.push(Token::SysCall);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_bkd.is_match(line) {
             tokens.push(Token::BranchKeyDown);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_bku.is_match(line) {
             tokens.push(Token::BranchKeyUp);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_gkd.is_match(line) {
             tokens.push(Token::GetKey);
-            line = &line[4..]
+            line = &line[3..]
         } else if r_gdt.is_match(line) {
             tokens.push(Token::GetTimer);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_sdt.is_match(line) {
             tokens.push(Token::SetTimer);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_sst.is_match(line) {
             tokens.push(Token::SetSound);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_gca.is_match(line) {
             tokens.push(Token::GetCharAddr);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_clr.is_match(line) {
             tokens.push(Token::Clear);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_ret.is_match(line) {
             tokens.push(Token::Return);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_bne.is_match(line) {
             tokens.push(Token::BranchNotEqual);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_mov.is_match(line) {
             tokens.push(Token::Move);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_movi.is_match(line) {
             tokens.push(Token::MoveI);
             line = &line[4..];
@@ -274,45 +306,49 @@ fn lex(source: &str) -> Result<Vec<Token>, LexError> {
             line = &line[4..];
         } else if r_add.is_match(line) {
             tokens.push(Token::Add);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_sub.is_match(line) {
             tokens.push(Token::Sub);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_xor.is_match(line) {
             tokens.push(Token::Xor);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_and.is_match(line) {
             tokens.push(Token::And);
-            line = &line[4..];
+            line = &line[3..];
         } else if r_or.is_match(line) {
             tokens.push(Token::Or);
-            line = &line[3..];
+            line = &line[2..];
         } else if r_be.is_match(line) {
             tokens.push(Token::BranchEqual);
-            line = &line[3..];
+            line = &line[2..];
         } else if r_sr.is_match(line) {
             tokens.push(Token::ShiftRight);
-            line = &line[3..];
+            line = &line[2..];
         } else if r_sl.is_match(line) {
             tokens.push(Token::ShiftLeft);
-            line = &line[3..];
+            line = &line[2..];
         } else if r_jr.is_match(line) {
             tokens.push(Token::JumpReg);
-            line = &line[3..];
+            line = &line[2..];
         } else if r_sb.is_match(line) {
             tokens.push(Token::Store);
-            line = &line[3..];
+            line = &line[2..];
         } else if r_lb.is_match(line) {
             tokens.push(Token::Load);
-            line = &line[3..];
+            line = &line[2..];
         } else if r_j.is_match(line) {
             tokens.push(Token::Jump);
-            line = &line[2..];
+            line = &line[1..];
+        } else if r_byte.is_match(line) {
+            tokens.push(Token::Byte);
+            line = &line[5..];
         } else if r_comma.is_match(line) {
             tokens.push(Token::Comma);
-            line = &line[2..];
+            line = &line[1..];
         } else if r_comment.is_match(line) {
-            break;
+            // skip to the end of the line, keeping the newline
+            line = &line[line.find('\n').unwrap_or(line.len())..];
         } else if r_label_def.is_match(line) {
             let name: &str = &r_label_def.captures(line).unwrap()["name"];
             tokens.push(Token::Label(name.to_string()));
@@ -328,224 +364,277 @@ fn lex(source: &str) -> Result<Vec<Token>, LexError> {
             // make skip by number of ws characters found
             line = &line[(r_whitespace.captures(line).unwrap()[0].len())..];
         } else {
-            return Err(LexError::IllegalToken);
+            return Err(lex_error(source, line, LexError::IllegalToken));
         }
     }
 
     Ok(tokens)
 }
 
-fn parse(mut ast: &[Token]) -> Result<Prog, ParseError> {
+fn parse(mut ast: &[Token]) -> Result<Prog, AsmError> {
     let mut instr_list: Vec<Instr> = Vec::new();
     let mut label_map: Vec<(String, u16)> = Vec::new();
-    let mut symbol_list: Vec<String> = Vec::new();
-    let mut address: u16 = 0;
+    // symbols used and the line each one is on
+    let mut symbol_list: Vec<(String, usize)> = Vec::new();
+    let mut line_map: LineMap = Vec::new();
+    let mut address: u16 = 0x200;
+    let mut line: usize = 1;
 
     while !ast.is_empty() {
+        let start = ast;
+        let start_address = address;
         match ast {
             [Token::SysCall, Token::Value(v), Token::EndLine, ..] => {
                 instr_list.push(Instr::CallMachineCode(*v));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::Clear, Token::EndLine, ..] => {
                 instr_list.push(Instr::ClearScreen);
                 ast = &ast[2..];
-                address += 1;
+                address += 2;
             }
             [Token::Return, Token::EndLine, ..] => {
                 instr_list.push(Instr::Return);
                 ast = &ast[2..];
-                address += 1;
+                address += 2;
             }
             [Token::Jump, Token::Value(v), Token::EndLine, ..] => {
                 instr_list.push(Instr::Jump(*v));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::JumpReg, Token::Value(v), Token::EndLine, ..] => {
                 instr_list.push(Instr::JumpReg(*v));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::Jump, Token::Symbol(s), Token::EndLine, ..] => {
                 instr_list.push(Instr::JumpLabel(s.clone()));
-                symbol_list.push(s.clone());
+                symbol_list.push((s.clone(), line));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::JumpReg, Token::Symbol(s), Token::EndLine, ..] => {
                 instr_list.push(Instr::JumpRegLabel(s.clone()));
-                symbol_list.push(s.clone());
+                symbol_list.push((s.clone(), line));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::Call, Token::Value(v), Token::EndLine, ..] => {
                 instr_list.push(Instr::Call(*v));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
+            }
+            [Token::Call, Token::Symbol(s), Token::EndLine, ..] => {
+                instr_list.push(Instr::CallLabel(s.clone()));
+                symbol_list.push((s.clone(), line));
+                ast = &ast[3..];
+                address += 2;
             }
             [Token::BranchEqual, Token::Register(r1), Token::Comma, Token::Value(v), Token::EndLine, ..] =>
             {
                 instr_list.push(Instr::IfEqualImm(*r1, *v as u8));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::BranchEqual, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::IfEqualReg(*r1, *r2 as u8));
+                instr_list.push(Instr::IfEqualReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::BranchNotEqual, Token::Register(r1), Token::Comma, Token::Value(v), Token::EndLine, ..] =>
             {
                 instr_list.push(Instr::IfNotEqualImm(*r1, *v as u8));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::BranchNotEqual, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::IfNotEqualReg(*r1, *r2 as u8));
+                instr_list.push(Instr::IfNotEqualReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::Move, Token::Register(r1), Token::Comma, Token::Value(v), Token::EndLine, ..] =>
             {
                 instr_list.push(Instr::SetImm(*r1, *v as u8));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::Move, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::SetReg(*r1, *r2 as u8));
+                instr_list.push(Instr::SetReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::MoveI, Token::Value(v), Token::EndLine, ..] => {
                 instr_list.push(Instr::SetI(*v));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
+            }
+            [Token::MoveI, Token::Symbol(s), Token::EndLine, ..] => {
+                instr_list.push(Instr::SetILabel(s.clone()));
+                symbol_list.push((s.clone(), line));
+                ast = &ast[3..];
+                address += 2;
             }
             [Token::AddI, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::AddIReg(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::Add, Token::Register(r1), Token::Comma, Token::Value(v), Token::EndLine, ..] => {
                 instr_list.push(Instr::AddImm(*r1, *v as u8));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::Add, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::AddReg(*r1, *r2 as u8));
+                instr_list.push(Instr::AddReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::Sub, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::SubReg(*r1, *r2 as u8));
+                instr_list.push(Instr::SubReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::SubRegNeg, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::SetSubReg(*r1, *r2 as u8));
+                instr_list.push(Instr::SetSubReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::Or, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::OrReg(*r1, *r2 as u8));
+                instr_list.push(Instr::OrReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::And, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::AndReg(*r1, *r2 as u8));
+                instr_list.push(Instr::AndReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::Xor, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
             {
-                instr_list.push(Instr::XorReg(*r1, *r2 as u8));
+                instr_list.push(Instr::XorReg(*r1, *r2));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::ShiftLeft, Token::Register(r1), Token::Comma, Token::Value(v), Token::EndLine, ..] =>
             {
                 instr_list.push(Instr::ShiftLeft(*r1, *v as u8));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::ShiftRight, Token::Register(r1), Token::Comma, Token::Value(v), Token::EndLine, ..] =>
             {
                 instr_list.push(Instr::ShiftRight(*r1, *v as u8));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
+            }
+            [Token::ShiftLeft, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
+            {
+                instr_list.push(Instr::ShiftLeft(*r1, *r2));
+                ast = &ast[5..];
+                address += 2;
+            }
+            [Token::ShiftRight, Token::Register(r1), Token::Comma, Token::Register(r2), Token::EndLine, ..] =>
+            {
+                instr_list.push(Instr::ShiftRight(*r1, *r2));
+                ast = &ast[5..];
+                address += 2;
             }
             [Token::Rand, Token::Register(r1), Token::Comma, Token::Value(v), Token::EndLine, ..] =>
             {
                 instr_list.push(Instr::Rand(*r1, *v as u8));
                 ast = &ast[5..];
-                address += 1;
+                address += 2;
             }
             [Token::Draw, Token::Register(r1), Token::Comma, Token::Register(r2), Token::Comma, Token::Value(v), Token::EndLine, ..] =>
             {
                 instr_list.push(Instr::Draw(*r1, *r2, *v as u8));
                 ast = &ast[7..];
-                address += 1;
+                address += 2;
             }
             [Token::BranchKeyUp, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::IfKey(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::BranchKeyDown, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::IfNotKey(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
 
             [Token::GetTimer, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::GetTimer(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::SetTimer, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::SetTimer(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::SetSound, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::SetSound(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::GetCharAddr, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::SetICharAddr(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::StoreBCD, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::StoreDecimal(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::Store, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::StoreReg(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::Load, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::LoadReg(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
             }
             [Token::GetKey, Token::Register(r1), Token::EndLine, ..] => {
                 instr_list.push(Instr::GetKey(*r1));
                 ast = &ast[3..];
-                address += 1;
+                address += 2;
+            }
+            [Token::Byte, ..] => {
+                // comma separated values up to the end of the line
+                let mut bytes = Vec::new();
+                let mut rest = &ast[1..];
+                loop {
+                    match rest {
+                        [Token::Value(v), end, ..] if *v <= 0xFF => {
+                            bytes.push(*v as u8);
+                            rest = &rest[2..];
+                            match end {
+                                Token::Comma => {}
+                                Token::EndLine => break,
+                                _ => {
+                                    return Err(parse_error(line, ParseError::MalformedInstruction))
+                                }
+                            }
+                        }
+                        _ => return Err(parse_error(line, ParseError::MalformedInstruction)),
+                    }
+                }
+                address += bytes.len() as u16;
+                instr_list.push(Instr::Bytes(bytes));
+                ast = rest;
             }
             [Token::EndLine, ..] => ast = &ast[1..],
             [Token::Label(s), ..] => {
@@ -554,94 +643,140 @@ fn parse(mut ast: &[Token]) -> Result<Prog, ParseError> {
             }
             _ => {
                 //println!("{:?}", ast[0]);
-                return Err(ParseError::MalformedInstruction);
+                return Err(parse_error(line, ParseError::MalformedInstruction));
             }
         }
+
+        if address != start_address {
+            line_map.push((start_address, line));
+        }
+        let consumed = &start[..start.len() - ast.len()];
+        line += consumed.iter().filter(|&t| *t == Token::EndLine).count();
     }
 
-    for s in symbol_list {
+    for (s, line) in symbol_list {
         if label_map.iter().find(|&l| l.0 == *s).is_none() {
-            return Err(ParseError::UndefinedSymbol);
+            return Err(parse_error(line, ParseError::UndefinedSymbol));
         }
     }
 
     Ok(Prog {
         instructions: instr_list,
         label_map,
+        line_map,
     })
 }
 
+// labels are checked to exist while parsing
+fn label_addr(labels: &[(String, u16)], name: &str) -> u16 {
+    labels.iter().find(|&x| x.0 == *name).unwrap().1
+}
+
+fn encode(instr: &Instr, labels: &[(String, u16)]) -> [u8; 2] {
+    match instr {
+        Instr::CallMachineCode(v) => [(v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
+        Instr::ClearScreen => [0x00, 0xE0],
+        Instr::Return => [0x00, 0xEE],
+        Instr::Jump(v) => [0x10 | (v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
+        Instr::Call(v) => [0x20 | (v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
+        Instr::IfEqualImm(x, v) => [0x30 | (x & 0x0F), *v],
+        Instr::IfNotEqualImm(x, v) => [0x40 | (x & 0x0F), *v],
+        Instr::IfEqualReg(x, y) => [0x50 | (x & 0x0F), y << 4],
+        Instr::SetImm(x, v) => [0x60 | (x & 0x0F), *v],
+        Instr::AddImm(x, v) => [0x70 | (x & 0x0F), *v],
+        Instr::SetReg(x, y) => [0x80 | (x & 0x0F), y << 4],
+        Instr::OrReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x01],
+        Instr::AndReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x02],
+        Instr::XorReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x03],
+        Instr::AddReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x04],
+        Instr::SubReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x05],
+        Instr::ShiftRight(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x06],
+        Instr::SetSubReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x07],
+        Instr::ShiftLeft(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x0E],
+        Instr::IfNotEqualReg(x, y) => [0x90 | (x & 0x0F), y << 4],
+        Instr::SetI(v) => [0xA0 | (v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
+        Instr::JumpReg(v) => [0xB0 | (v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
+        Instr::Rand(x, v) => [0xC0 | (x & 0x0F), *v],
+        Instr::Draw(x, y, v) => [0xD0 | (x & 0x0F), (y << 4) | (v & 0x0F)],
+        Instr::IfKey(x) => [0xE0 | (x & 0x0F), 0x9E],
+        Instr::IfNotKey(x) => [0xE0 | (x & 0x0F), 0xA1],
+        Instr::GetTimer(x) => [0xF0 | (x & 0x0F), 0x07],
+        Instr::GetKey(x) => [0xF0 | (x & 0x0F), 0x0A],
+        Instr::SetTimer(x) => [0xF0 | (x & 0x0F), 0x15],
+        Instr::SetSound(x) => [0xF0 | (x & 0x0F), 0x18],
+        Instr::AddIReg(x) => [0xF0 | (x & 0x0F), 0x1E],
+        Instr::SetICharAddr(x) => [0xF0 | (x & 0x0F), 0x29],
+        Instr::StoreDecimal(x) => [0xF0 | (x & 0x0F), 0x33],
+        Instr::StoreReg(x) => [0xF0 | (x & 0x0F), 0x55],
+        Instr::LoadReg(x) => [0xF0 | (x & 0x0F), 0x65],
+        Instr::JumpLabel(s) => encode(&Instr::Jump(label_addr(labels, s)), labels),
+        Instr::JumpRegLabel(s) => encode(&Instr::JumpReg(label_addr(labels, s)), labels),
+        Instr::CallLabel(s) => encode(&Instr::Call(label_addr(labels, s)), labels),
+        Instr::SetILabel(s) => encode(&Instr::SetI(label_addr(labels, s)), labels),
+        Instr::Bytes(_) => unreachable!("compile copies data out whole"),
+    }
+}
+
 fn compile(isa: &Prog) -> Vec<u8> {
-    let bin: Vec<u8> = isa
-        .instructions
+    isa.instructions
         .iter()
-        .map(|instr| match instr {
-            Instr::CallMachineCode(v) => [(v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
-            Instr::ClearScreen => [0x00, 0xE0],
-            Instr::Return => [0x00, 0xEE],
-            Instr::Jump(v) => [0x10 | (v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
-            Instr::Call(v) => [0x20 | (v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
-            Instr::IfEqualImm(x, v) => [0x30 | (x & 0x0F), *v as u8],
-            Instr::IfNotEqualImm(x, v) => [0x40 | (x & 0x0F), *v as u8],
-            Instr::IfEqualReg(x, y) => [0x50 | (x & 0x0F), y << 4],
-            Instr::SetImm(x, v) => [0x60 | (x & 0x0F), *v as u8],
-            Instr::AddImm(x, v) => [0x70 | (x & 0x0F), *v as u8],
-            Instr::SetReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x00],
-            Instr::OrReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x01],
-            Instr::AndReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x02],
-            Instr::XorReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x03],
-            Instr::AddReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x04],
-            Instr::SubReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x05],
-            Instr::ShiftRight(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x06],
-            Instr::SetSubReg(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x07],
-            Instr::ShiftLeft(x, y) => [0x80 | (x & 0x0F), (y << 4) | 0x0E],
-            Instr::IfNotEqualReg(x, y) => [0x90 | (x & 0x0F), y << 4],
-            Instr::SetI(v) => [0xA0 | (v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
-            Instr::JumpReg(v) => [0xB0 | (v >> 8) as u8 & 0x0F, (v & 0x00FF) as u8],
-            Instr::Rand(x, v) => [0xC0 | (x & 0x0F), *v as u8],
-            Instr::Draw(x, y, v) => [0xD0 | (x & 0x0F), (y << 4) | (v & 0x000F) as u8],
-            Instr::IfKey(x) => [0xE | (x & 0x0F), 0x9E],
-            Instr::IfNotKey(x) => [0xE | (x & 0x0F), 0xA1],
-            Instr::GetTimer(x) => [0xF0 | (x & 0x0F), 0x07],
-            Instr::GetKey(x) => [0xF0 | (x & 0x0F), 0x0A],
-            Instr::SetTimer(x) => [0xF0 | (x & 0x0F), 0x15],
-            Instr::SetSound(x) => [0xF0 | (x & 0x0F), 0x18],
-            Instr::AddIReg(x) => [0xF0 | (x & 0x0F), 0x1E],
-            Instr::SetICharAddr(x) => [0xF0 | (x & 0x0F), 0x29],
-            Instr::StoreDecimal(x) => [0xF0 | (x & 0x0F), 0x33],
-            Instr::StoreReg(x) => [0xF0 | (x & 0x0F), 0x55],
-            Instr::LoadReg(x) => [0xF0 | (x & 0x0F), 0x65],
-            Instr::JumpLabel(s) => {
-                let target = isa.label_map.iter().find(|&x| x.0 == *s).unwrap().1 * 2 + 0x200;
-                [0x10 | (target >> 8) as u8 & 0x0F, (target & 0x00FF) as u8]
-            }
-            Instr::JumpRegLabel(s) => {
-                let target = isa.label_map.iter().find(|&x| x.0 == *s).unwrap().1 * 2 + 0x200;
-                [0xB0 | (target >> 8) as u8 & 0x0F, (target & 0x00FF) as u8]
-            }
+        .flat_map(|instr| match instr {
+            Instr::Bytes(bytes) => bytes.clone(),
+            _ => encode(instr, &isa.label_map).to_vec(),
         })
-        .flatten()
-        .collect();
-
-    bin
+        .collect()
 }
 
-pub fn assemble(source: &str, print_debug: bool) -> Result<Vec<u8>, CompileError> {
-    let ast = match lex(source) {
-        Ok(v) => v,
-        Err(e) => return Err(CompileError::LexError(e)),
-    };
+pub fn assemble(source: &str, print_debug: bool) -> Result<(Vec<u8>, LineMap), AsmError> {
+    let ast = lex(source)?;
     if print_debug {
         println!("ast: {:?}", ast);
     }
 
-    let isa = match parse(&ast) {
-        Ok(v) => v,
-        Err(e) => return Err(CompileError::ParseError(e)),
-    };
+    let isa = parse(&ast)?;
     if print_debug {
         println!("isa: {:?}", isa);
     }
 
-    Ok(compile(&isa))
+    Ok((compile(&isa), isa.line_map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assemble;
+
+    fn bytes(source: &str) -> Vec<u8> {
+        assemble(source, false).unwrap().0
+    }
+
+    #[test]
+    fn mnemonics_without_operands_keep_their_newline() {
+        assert_eq!(
+            bytes("clr\nret\nclr\n"),
+            [0x00, 0xE0, 0x00, 0xEE, 0x00, 0xE0]
+        );
+    }
+
+    #[test]
+    fn mnemonics_of_every_length_take_operands() {
+        let source = "j 0x208\nor v1, v2\nsys 0x123\nrand v3, 0x0F\n";
+        assert_eq!(
+            bytes(source),
+            [0x12, 0x08, 0x81, 0x21, 0x01, 0x23, 0xC3, 0x0F]
+        );
+    }
+
+    #[test]
+    fn comments_end_at_the_line() {
+        let source = "mov v1, 0x05\n# set v2 next\nmov v2, 0x06 # and a trailing one\nret\n";
+        assert_eq!(bytes(source), [0x61, 0x05, 0x62, 0x06, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn labels_and_line_map_use_rom_addresses() {
+        let source = "clr\n# skip the line\nloop:\nj loop\n";
+        let (rom, lines) = assemble(source, false).unwrap();
+        assert_eq!(rom, [0x00, 0xE0, 0x12, 0x02]);
+        assert_eq!(lines, [(0x200, 1), (0x202, 4)]);
+    }
 }
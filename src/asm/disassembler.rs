@@ -0,0 +1,157 @@
+// Turns a rom back into chipc source. Code is found by following every path
+// from 0x200 through jumps, calls and skips, anything never reached is
+// written out as .byte data. Instructions chipc has no mnemonic for are
+// written as .byte too, so assembling the output gives back the same rom.
+use crate::core::disasm::decode;
+use crate::core::Platform;
+use std::collections::BTreeSet;
+
+const START: usize = 0x200;
+
+struct Code {
+    // offsets into the rom where a reached instruction starts
+    starts: BTreeSet<usize>,
+    // offsets that get a label, branch and index targets inside the rom
+    labels: BTreeSet<usize>,
+}
+
+fn word(rom: &[u8], offset: usize) -> u16 {
+    (rom[offset] as u16) << 8 | rom[offset + 1] as u16
+}
+
+fn is_skip(instr: u16) -> bool {
+    match instr >> 12 {
+        0x3 | 0x4 => true,
+        0x5 | 0x9 => instr & 0x000F == 0,
+        0xE => matches!(instr & 0x00FF, 0x9E | 0xA1),
+        _ => false,
+    }
+}
+
+fn trace(rom: &[u8]) -> Code {
+    let mut starts = BTreeSet::new();
+    let mut covered = vec![false; rom.len()];
+    let mut targets = BTreeSet::new();
+    let mut work = vec![START];
+
+    while let Some(addr) = work.pop() {
+        let offset = match addr.checked_sub(START) {
+            Some(o) if o + 1 < rom.len() => o,
+            _ => continue,
+        };
+        // already seen, or the middle of another instruction
+        if covered[offset] || covered[offset + 1] {
+            continue;
+        }
+        starts.insert(offset);
+        covered[offset] = true;
+        covered[offset + 1] = true;
+
+        let instr = word(rom, offset);
+        let target = (instr & 0x0FFF) as usize;
+        match instr >> 12 {
+            0x0 if instr == 0x00EE || instr == 0x00FD => {}
+            0x1 | 0xB => {
+                targets.insert(target);
+                work.push(target);
+            }
+            0x2 => {
+                targets.insert(target);
+                work.push(target);
+                work.push(addr + 2);
+            }
+            0xA => {
+                targets.insert(target);
+                work.push(addr + 2);
+            }
+            _ if is_skip(instr) => {
+                work.push(addr + 2);
+                work.push(addr + 4);
+            }
+            _ => work.push(addr + 2),
+        }
+    }
+
+    // a target in the middle of an instruction can't have a label
+    let labels = targets
+        .into_iter()
+        .filter_map(|addr| addr.checked_sub(START))
+        .filter(|offset| *offset < rom.len())
+        .filter(|offset| starts.contains(offset) || !covered[*offset])
+        .collect();
+    Code { starts, labels }
+}
+
+fn byte_line(bytes: &[u8]) -> String {
+    let values: Vec<String> = bytes.iter().map(|b| format!("{:#04X}", b)).collect();
+    format!(".byte {}\n", values.join(", "))
+}
+
+pub fn disassemble(rom: &[u8]) -> String {
+    let code = trace(rom);
+    let target = |addr: u16| match (addr as usize).checked_sub(START) {
+        Some(offset) if code.labels.contains(&offset) => format!("L{:04X}", addr),
+        _ => format!("{:#05X}", addr),
+    };
+
+    let mut out = String::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        if code.labels.contains(&offset) {
+            out.push_str(&format!("L{:04X}:\n", offset + START));
+        }
+
+        if code.starts.contains(&offset) {
+            match decode(word(rom, offset), Platform::Chip8, &target) {
+                Some(text) => out.push_str(&format!("{}\n", text)),
+                None => out.push_str(&byte_line(&rom[offset..offset + 2])),
+            }
+            offset += 2;
+            continue;
+        }
+
+        // data runs up to the next instruction or label, 8 bytes a line
+        let mut end = offset + 1;
+        while end < rom.len()
+            && end - offset < 8
+            && !code.starts.contains(&end)
+            && !code.labels.contains(&end)
+        {
+            end += 1;
+        }
+        out.push_str(&byte_line(&rom[offset..end]));
+        offset = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+    use crate::asm::assemble;
+    use crate::core::rng::Rng;
+
+    fn round_trip(rom: &[u8]) {
+        let source = disassemble(rom);
+        let (assembled, _) = assemble(&source, false).unwrap();
+        assert_eq!(assembled, rom, "source:\n{}", source);
+    }
+
+    #[test]
+    fn code_and_data_round_trip() {
+        // loop over a sprite draw with a call into data and a schip opcode
+        round_trip(&[
+            0x00, 0xE0, 0xA2, 0x0C, 0x22, 0x0A, 0xD0, 0x15, 0x12, 0x02, 0x00, 0xFF, 0xF0, 0x90,
+            0xF0, 0x90, 0x90, 0x01,
+        ]);
+    }
+
+    #[test]
+    fn random_roms_round_trip() {
+        let mut rng = Rng::seeded(0xC8);
+        for len in (1..512).step_by(37) {
+            let rom: Vec<u8> = (0..len).map(|_| rng.next_u8(&[])).collect();
+            round_trip(&rom);
+        }
+    }
+}
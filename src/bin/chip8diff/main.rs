@@ -0,0 +1,37 @@
+// Runs a rom twice side by side, or once against a --trace file written by
+// chip8emu, and reports the first instruction where the two disagree. It
+// takes the same settings for running the rom as chip8emu but has no window
+// and doesn't look roms up in the rom database, so pass --platform and
+// --quirks when the rom needs them.
+use crate::options::{read_rom, Machine};
+use clap::Parser;
+
+#[path = "../chip8emu/options.rs"]
+mod options;
+mod tracediff;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    #[command(flatten)]
+    machine: Machine,
+
+    // Run the rom a second time with these quirks changed, as a preset or
+    // flip like vf_reset,index=x
+    #[arg(long, value_name = "quirks", required_unless_present = "trace")]
+    other_quirks: Option<String>,
+
+    // Compare the run against a trace file from chip8emu --trace
+    #[arg(long, value_name = "file", conflicts_with = "other_quirks")]
+    trace: Option<String>,
+
+    // Lines of trace to show before and after the first difference
+    #[arg(long, value_name = "lines", default_value_t = 5)]
+    context: usize,
+}
+
+pub fn main() -> Result<(), String> {
+    let mut args = Args::parse();
+    args.machine.rom = read_rom(&args.machine.filename)?;
+    tracediff::run(&args)
+}
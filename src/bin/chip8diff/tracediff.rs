@@ -0,0 +1,309 @@
+// Steps the two runs a cycle at a time and compares their trace lines. Both
+// runs get the same key presses, and unless --seed or --vip-rng is given
+// both use seed 0 so CXNN matches. A trace file only matches if it was
+// written with the same keys and seed.
+use crate::options::{create_emulator, key_events, Machine};
+use crate::Args;
+use chip8::core::rng::Rng;
+use chip8::core::{Chip8, Chip8Error, Chip8Mode};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+
+enum Source {
+    Emulator(Box<Chip8>),
+    File(Lines<BufReader<File>>),
+}
+
+struct Run {
+    name: String,
+    source: Source,
+    // lines before the current one, at most context of them
+    history: VecDeque<String>,
+    // lines after the first difference
+    after: Vec<String>,
+    error: Option<Chip8Error>,
+    ended: bool,
+}
+
+impl Run {
+    fn new(name: String, source: Source) -> Run {
+        Run {
+            name,
+            source,
+            history: VecDeque::new(),
+            after: Vec::new(),
+            error: None,
+            ended: false,
+        }
+    }
+
+    fn emulator(&self) -> Option<&Chip8> {
+        match &self.source {
+            Source::Emulator(emu) => Some(emu),
+            Source::File(_) => None,
+        }
+    }
+
+    fn waiting(&self) -> bool {
+        self.emulator()
+            .is_some_and(|emu| emu.mode == Chip8Mode::WaitingKey)
+    }
+
+    // lets a waiting emulator count the cycle without running anything
+    fn idle(&mut self) {
+        if let Source::Emulator(emu) = &mut self.source {
+            if emu.mode == Chip8Mode::WaitingKey {
+                let _ = emu.clock();
+            }
+        }
+    }
+
+    // why next_line gave None
+    fn stopped(&self) -> String {
+        if self.waiting() {
+            format!("{} is waiting for a key", self.name)
+        } else {
+            format!("{} has ended", self.name)
+        }
+    }
+
+    fn start_frame(&mut self, args: &Machine, frame: u64) {
+        if let Source::Emulator(emu) = &mut self.source {
+            for (key, down) in key_events(&args.presses, frame) {
+                if down {
+                    emu.key_down(key);
+                } else {
+                    emu.key_up(key);
+                }
+            }
+        }
+    }
+
+    fn end_frame(&mut self) {
+        if let Source::Emulator(emu) = &mut self.source {
+            emu.signal_new_frame();
+        }
+    }
+
+    // the line for the instruction about to run, None once the run is over
+    fn next_line(&mut self) -> Option<String> {
+        if self.ended {
+            return None;
+        }
+        match &mut self.source {
+            Source::Emulator(emu) if emu.mode == Chip8Mode::Running => Some(emu.trace_line()),
+            Source::Emulator(_) => None,
+            Source::File(lines) => match lines.next() {
+                Some(Ok(line)) => Some(line),
+                _ => {
+                    self.ended = true;
+                    None
+                }
+            },
+        }
+    }
+
+    // runs the instruction line belongs to
+    fn step(&mut self, line: Option<String>, context: usize, diverged: bool) {
+        let line = match line {
+            Some(line) => line,
+            None => return,
+        };
+        if diverged {
+            self.after.push(line);
+        } else {
+            self.history.push_back(line);
+            if self.history.len() > context + 1 {
+                self.history.pop_front();
+            }
+        }
+
+        if let Source::Emulator(emu) = &mut self.source {
+            if let Err(e) = emu.clock() {
+                self.error = Some(e);
+                self.ended = true;
+            }
+        }
+    }
+
+    fn print(&self) {
+        println!("{}:", self.name);
+        for (index, line) in self.history.iter().enumerate() {
+            let marker = if index + 1 == self.history.len() {
+                ">"
+            } else {
+                " "
+            };
+            println!("{} {}", marker, line);
+        }
+        for line in &self.after {
+            println!("  {}", line);
+        }
+        if let Some(e) = &self.error {
+            println!("  stopped with {}", e);
+        }
+    }
+}
+
+// the KEY:VALUE fields of a trace line, without the mnemonic
+fn fields(line: &str) -> Vec<(&str, &str)> {
+    let state = line.split(" ;").next().unwrap_or("");
+    state
+        .split_whitespace()
+        .filter_map(|field| field.split_once(':'))
+        .collect()
+}
+
+fn differences(a: &Run, line_a: &Option<String>, b: &Run, line_b: &Option<String>) -> Vec<String> {
+    let (line_a, line_b) = match (line_a, line_b) {
+        (None, None) => return Vec::new(),
+        (Some(_), None) => return vec![b.stopped()],
+        (None, Some(_)) => return vec![a.stopped()],
+        (Some(line_a), Some(line_b)) => (line_a, line_b),
+    };
+
+    let mut found = Vec::new();
+    let fields_b = fields(line_b);
+    for (key, value) in fields(line_a) {
+        match fields_b.iter().find(|(k, _)| *k == key) {
+            Some((_, other)) if *other != value => {
+                found.push(format!("{}: {} vs {}", key, value, other))
+            }
+            Some(_) => {}
+            None => found.push(format!("{}: {} vs missing", key, value)),
+        }
+    }
+
+    // memory is only known when both runs are emulators
+    if let (Some(emu_a), Some(emu_b)) = (a.emulator(), b.emulator()) {
+        let memory_a = emu_a.get_memory();
+        let memory_b = emu_b.get_memory();
+        let changed = memory_a
+            .iter()
+            .zip(memory_b)
+            .enumerate()
+            .filter(|(_, (x, y))| x != y);
+        for (addr, (x, y)) in changed.take(8) {
+            found.push(format!("memory {:04X}: {:02X} vs {:02X}", addr, x, y));
+        }
+    }
+    found
+}
+
+pub fn run(diff: &Args) -> Result<(), String> {
+    let args = &diff.machine;
+    if args.frames.is_none() && args.cycles.is_none() {
+        return Err("trace diffs need --frames or --cycles".to_string());
+    }
+
+    let mut emu = create_emulator(args)?;
+    if args.seed.is_none() && !args.vip_rng {
+        emu.set_rng(Rng::seeded(0));
+    }
+    let other = match (&diff.other_quirks, &diff.trace) {
+        (Some(changes), _) => {
+            let mut other = create_emulator(args)?;
+            other.quirks = emu.quirks.changed(changes)?;
+            if args.seed.is_none() && !args.vip_rng {
+                other.set_rng(Rng::seeded(0));
+            }
+            Run::new(
+                format!("quirks {}", changes),
+                Source::Emulator(Box::new(other)),
+            )
+        }
+        (None, Some(path)) => {
+            let file = File::open(path).map_err(|e| format!("could not open {}: {}", path, e))?;
+            Run::new(path.clone(), Source::File(BufReader::new(file).lines()))
+        }
+        (None, None) => unreachable!(),
+    };
+    let mut runs = [
+        Run::new("this run".to_string(), Source::Emulator(Box::new(emu))),
+        other,
+    ];
+
+    let context = diff.context;
+    let mut frame: u64 = 0;
+    let mut cycles: u64 = 0;
+    let mut first = None;
+    let mut trailing = 0;
+
+    'running: while args.frames.is_none_or(|max| frame < max) {
+        for run in &mut runs {
+            run.start_frame(args, frame);
+        }
+
+        for _ in 0..args.speed().instructions(frame) {
+            if args.cycles.is_some_and(|max| cycles >= max) || trailing > context {
+                break 'running;
+            }
+            // a run waiting on FX0A writes no trace lines, so there is
+            // nothing to compare until a key event in a later frame, but the
+            // cycles it spends waiting still count
+            if runs[0].waiting() && (runs[1].waiting() || runs[1].emulator().is_none()) {
+                for run in &mut runs {
+                    run.idle();
+                }
+                cycles += 1;
+                continue;
+            }
+            let line_a = runs[0].next_line();
+            let line_b = runs[1].next_line();
+            if line_a.is_none() && line_b.is_none() {
+                break 'running;
+            }
+
+            if first.is_none() {
+                let found = differences(&runs[0], &line_a, &runs[1], &line_b);
+                if !found.is_empty() {
+                    first = Some((cycles, found));
+                }
+            }
+            // the line that differs goes in history, the ones after it don't
+            let diverged = first.is_some() && trailing > 0;
+            if first.is_some() {
+                trailing += 1;
+            }
+            let [a, b] = &mut runs;
+            a.step(line_a, context, diverged);
+            b.step(line_b, context, diverged);
+            cycles += 1;
+        }
+
+        for run in &mut runs {
+            run.end_frame();
+        }
+        frame += 1;
+    }
+
+    match first {
+        Some((cycle, found)) => {
+            // the instruction before the first differing line caused it
+            let history = &runs[0].history;
+            match history.len().checked_sub(2).map(|index| &history[index]) {
+                Some(line) => {
+                    let (state, text) = line.split_once(" ;").unwrap_or((line, ""));
+                    let op: Vec<&str> = state.split_whitespace().skip(1).take(2).collect();
+                    println!(
+                        "first difference at cycle {}, after {} ;{}",
+                        cycle,
+                        op.join(" "),
+                        text
+                    );
+                }
+                None => println!("first difference at cycle {}:", cycle),
+            }
+            for difference in found {
+                println!("  {}", difference);
+            }
+            println!();
+            for run in &runs {
+                run.print();
+            }
+        }
+        None => println!("no differences in {} cycles", cycles),
+    }
+    Ok(())
+}
@@ -0,0 +1,580 @@
+// Debug adapter protocol server so editors can debug chipc programs by
+// source line. Lines are mapped to addresses with the file written by
+// `chipc --map`, found next to the rom as name.map unless launch gives one.
+//
+// The emulator is the only thread, with id 1. Its stack frames are pc and
+// then the call site of every return address on the stack.
+use crate::debug::{ClientAction, DebugClient, Debugger, StopReason, Watchpoint};
+use crate::options::read_rom;
+use crate::{create_emulator, Args};
+use chip8::core::disasm::disassemble;
+use chip8::core::{Chip8, Chip8Error};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+const THREAD_ID: u64 = 1;
+const REGISTERS_REF: u64 = 1;
+
+struct SourceMap {
+    source: PathBuf,
+    // sorted by address
+    lines: Vec<(u16, usize)>,
+}
+
+impl SourceMap {
+    fn load(path: &Path) -> Result<SourceMap, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut source = None;
+        let mut lines = Vec::new();
+        for entry in text.lines() {
+            if let Some(path) = entry.strip_prefix("source ") {
+                source = Some(PathBuf::from(path));
+                continue;
+            }
+            let parsed = entry.split_once(' ').and_then(|(addr, line)| {
+                Some((u16::from_str_radix(addr, 16).ok()?, line.parse().ok()?))
+            });
+            match parsed {
+                Some(pair) => lines.push(pair),
+                None => return Err(format!("{}: bad entry {}", path.display(), entry)),
+            }
+        }
+        lines.sort();
+
+        match source {
+            Some(source) => Ok(SourceMap { source, lines }),
+            None => Err(format!("{}: no source line", path.display())),
+        }
+    }
+
+    fn line_for(&self, addr: u16) -> Option<usize> {
+        self.lines
+            .binary_search_by_key(&addr, |(a, _)| *a)
+            .ok()
+            .map(|index| self.lines[index].1)
+    }
+
+    // the first instruction on line, or on the next line holding one
+    fn addr_for(&self, line: usize) -> Option<(u16, usize)> {
+        self.lines
+            .iter()
+            .filter(|(_, l)| *l >= line)
+            .min_by_key(|(addr, l)| (*l, *addr))
+            .copied()
+    }
+
+    fn is_source(&self, path: &str) -> bool {
+        fs::canonicalize(path).is_ok_and(|p| p == self.source)
+    }
+}
+
+pub struct DapServer {
+    args: Args,
+    messages: Receiver<Value>,
+    out: Box<dyn Write + Send>,
+    seq: u64,
+    connected: bool,
+    halted: bool,
+    stop_on_entry: bool,
+    map: Option<SourceMap>,
+    // events that have to follow the response being handled
+    pending: Vec<(&'static str, Value)>,
+}
+
+// reads content-length framed messages until the client goes away
+fn read_messages(mut input: impl BufRead, sender: Sender<Value>) {
+    loop {
+        let mut len = None;
+        loop {
+            let mut header = String::new();
+            match input.read_line(&mut header) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+
+        let mut body = vec![0; len.unwrap_or(0)];
+        if input.read_exact(&mut body).is_err() {
+            return;
+        }
+        if let Ok(message) = serde_json::from_slice(&body) {
+            if sender.send(message).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// memory references are sent as hex addresses like 0x0200
+fn parse_reference(value: &Value) -> Option<i64> {
+    let text = value.as_str()?;
+    i64::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+impl DapServer {
+    // stdio, or waits for one client on a local address. The emulator stays
+    // halted until the client has finished configuring breakpoints
+    pub fn listen(transport: &str, args: &Args) -> Result<DapServer, String> {
+        let (sender, messages) = mpsc::channel();
+        let out: Box<dyn Write + Send> = if transport == "stdio" {
+            thread::spawn(move || read_messages(io::stdin().lock(), sender));
+            Box::new(io::stdout())
+        } else {
+            let listener = TcpListener::bind(transport)
+                .map_err(|e| format!("could not listen on {}: {}", transport, e))?;
+            eprintln!("waiting for a debug adapter client on {}", transport);
+            let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
+            let reader = stream.try_clone().map_err(|e| e.to_string())?;
+            thread::spawn(move || read_messages(BufReader::new(reader), sender));
+            Box::new(stream)
+        };
+
+        Ok(DapServer {
+            args: args.clone(),
+            messages,
+            out,
+            seq: 0,
+            connected: true,
+            halted: true,
+            stop_on_entry: false,
+            map: None,
+            pending: Vec::new(),
+        })
+    }
+
+    fn send(&mut self, mut message: Value) {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        let _ = write!(self.out, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let _ = self.out.flush();
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn send_pending(&mut self) {
+        for (event, body) in std::mem::take(&mut self.pending) {
+            self.send_event(event, body);
+        }
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response);
+        self.send_pending();
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) {
+        self.halted = true;
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(text) = text {
+            body["text"] = json!(text);
+        }
+        self.pending.push(("stopped", body));
+    }
+
+    fn stop_body(reason: &StopReason) -> (&'static str, Option<String>) {
+        match reason {
+            StopReason::Breakpoint(_) => ("breakpoint", None),
+            StopReason::Watchpoint { watch, old, new } => {
+                let name = match watch {
+                    Watchpoint::Memory(addr) => format!("memory {:04X}", addr),
+                    Watchpoint::Register(reg) => format!("v{:X}", reg),
+                };
+                (
+                    "data breakpoint",
+                    Some(format!("{} changed {:02X} -> {:02X}", name, old, new)),
+                )
+            }
+            StopReason::Stepped => ("step", None),
+        }
+    }
+
+    // runs a single instruction and queues the stopped event for it
+    fn step(&mut self, emu: &mut Chip8, debugger: &mut Debugger) {
+        debugger.cancel_step();
+        match debugger.clock(emu) {
+            Ok(Some(reason)) => {
+                let (reason, text) = DapServer::stop_body(&reason);
+                self.stopped(reason, text);
+            }
+            Ok(None) => self.stopped("step", None),
+            Err(e) => self.stopped("exception", Some(e.to_string())),
+        }
+    }
+
+    fn frame(&self, emu: &Chip8, id: usize, addr: u16) -> Value {
+        let (text, _) = disassemble(emu.get_memory(), addr as usize, emu.get_platform());
+        let mut frame = json!({
+            "id": id,
+            "name": format!("{:04X} {}", addr, text),
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": format!("0x{:04X}", addr),
+        });
+        let line = self.map.as_ref().and_then(|map| map.line_for(addr));
+        if let (Some(map), Some(line)) = (&self.map, line) {
+            frame["line"] = json!(line);
+            frame["column"] = json!(1);
+            frame["source"] = json!({ "path": map.source });
+        }
+        frame
+    }
+
+    fn launch(&mut self, arguments: &Value, emu: &mut Chip8) -> Result<Value, String> {
+        if let Some(program) = arguments["program"].as_str() {
+            self.args.machine.filename = program.to_string();
+            self.args.machine.rom = read_rom(program)?;
+            *emu = create_emulator(&self.args)?;
+        }
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+
+        let map_path = match arguments["map"].as_str() {
+            Some(path) => PathBuf::from(path),
+            None => Path::new(&self.args.machine.filename).with_extension("map"),
+        };
+        match SourceMap::load(&map_path) {
+            Ok(map) => self.map = Some(map),
+            Err(e) => {
+                self.map = None;
+                self.pending.push((
+                    "output",
+                    json!({
+                        "category": "console",
+                        "output": format!("no source map, line breakpoints won't work: {}\n", e),
+                    }),
+                ));
+            }
+        }
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value, debugger: &mut Debugger) -> Value {
+        let path = arguments["source"]["path"].as_str().unwrap_or("");
+        let lines: Vec<usize> = arguments["breakpoints"]
+            .as_array()
+            .map(|list| {
+                list.iter()
+                    .filter_map(|b| b["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // breakpoints come a whole file at a time and there is only one file
+        debugger.breakpoints.clear();
+        let map = self.map.as_ref().filter(|map| map.is_source(path));
+        let breakpoints: Vec<Value> = lines
+            .iter()
+            .map(|line| match map.and_then(|map| map.addr_for(*line)) {
+                Some((addr, actual)) => {
+                    debugger.breakpoints.push(addr);
+                    json!({
+                        "verified": true,
+                        "line": actual,
+                        "instructionReference": format!("0x{:04X}", addr),
+                    })
+                }
+                None => json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "no instruction at or after this line",
+                }),
+            })
+            .collect();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn variables(&self, emu: &Chip8) -> Value {
+        let mut variables: Vec<Value> = emu
+            .get_registers()
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                json!({
+                    "name": format!("V{:X}", index),
+                    "value": format!("0x{:02X}", value),
+                    "variablesReference": 0,
+                })
+            })
+            .collect();
+        variables.push(json!({
+            "name": "I",
+            "value": format!("0x{:04X}", emu.get_index()),
+            "variablesReference": 0,
+            "memoryReference": format!("0x{:04X}", emu.get_index()),
+        }));
+        let others = [
+            ("PC", format!("0x{:04X}", emu.get_pc())),
+            ("SP", emu.get_stack().len().to_string()),
+            ("DT", format!("0x{:02X}", emu.get_delay_timer())),
+            ("ST", format!("0x{:02X}", emu.get_sound_timer())),
+        ];
+        for (name, value) in others {
+            variables.push(json!({ "name": name, "value": value, "variablesReference": 0 }));
+        }
+        json!({ "variables": variables })
+    }
+
+    fn read_memory(&self, arguments: &Value, emu: &Chip8) -> Result<Value, String> {
+        let start = parse_reference(&arguments["memoryReference"])
+            .ok_or("bad memory reference")?
+            .saturating_add(arguments["offset"].as_i64().unwrap_or(0));
+        let memory = emu.get_memory();
+        // nothing past the size of memory could ever be read
+        let count = arguments["count"]
+            .as_u64()
+            .unwrap_or(0)
+            .min(memory.len() as u64) as usize;
+
+        let start = start.clamp(0, memory.len() as i64) as usize;
+        let end = start.saturating_add(count).min(memory.len());
+        Ok(json!({
+            "address": format!("0x{:04X}", start),
+            "data": base64(&memory[start..end]),
+            "unreadableBytes": count - (end - start),
+        }))
+    }
+
+    fn disassemble(&self, arguments: &Value, emu: &Chip8) -> Result<Value, String> {
+        let mut addr = parse_reference(&arguments["memoryReference"])
+            .ok_or("bad memory reference")?
+            .saturating_add(arguments["offset"].as_i64().unwrap_or(0))
+            .saturating_add(
+                arguments["instructionOffset"]
+                    .as_i64()
+                    .unwrap_or(0)
+                    .saturating_mul(2),
+            );
+        let memory = emu.get_memory();
+        // one instruction per byte is more than memory can hold
+        let count = arguments["instructionCount"]
+            .as_u64()
+            .unwrap_or(0)
+            .min(memory.len() as u64);
+
+        let mut instructions = Vec::new();
+        for _ in 0..count {
+            if addr < 0 || addr as usize >= memory.len() {
+                instructions.push(json!({
+                    "address": format!("0x{:04X}", addr.max(0)),
+                    "instruction": "??",
+                    "presentationHint": "invalid",
+                }));
+                addr = addr.saturating_add(2);
+                continue;
+            }
+            let (text, len) = disassemble(memory, addr as usize, emu.get_platform());
+            let end = (addr as usize + len).min(memory.len());
+            let bytes: String = memory[addr as usize..end]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect();
+            let mut instruction = json!({
+                "address": format!("0x{:04X}", addr),
+                "instructionBytes": bytes,
+                "instruction": text,
+            });
+            let line = self.map.as_ref().and_then(|map| map.line_for(addr as u16));
+            if let (Some(map), Some(line)) = (&self.map, line) {
+                instruction["line"] = json!(line);
+                instruction["location"] = json!({ "path": map.source });
+            }
+            instructions.push(instruction);
+            addr = addr.saturating_add(len as i64);
+        }
+        Ok(json!({ "instructions": instructions }))
+    }
+
+    fn handle_request(
+        &mut self,
+        request: &Value,
+        emu: &mut Chip8,
+        debugger: &mut Debugger,
+    ) -> ClientAction {
+        let arguments = &request["arguments"];
+        let result = match request["command"].as_str().unwrap_or("") {
+            "initialize" => {
+                self.pending.push(("initialized", json!({})));
+                Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsReadMemoryRequest": true,
+                    "supportsDisassembleRequest": true,
+                    "supportsInstructionBreakpoints": false,
+                }))
+            }
+            "launch" => self.launch(arguments, emu),
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments, debugger)),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.stopped("entry", None);
+                } else {
+                    self.halted = false;
+                }
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "chip8" }] })),
+            "stackTrace" => {
+                let mut frames = vec![self.frame(emu, 0, emu.get_pc())];
+                for (depth, ret) in emu.get_stack().iter().rev().enumerate() {
+                    frames.push(self.frame(emu, depth + 1, ret.wrapping_sub(2)));
+                }
+                Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+            }
+            "scopes" => Ok(json!({
+                "scopes": [{
+                    "name": "Registers",
+                    "variablesReference": REGISTERS_REF,
+                    "expensive": false,
+                }]
+            })),
+            "variables" => match arguments["variablesReference"].as_u64() {
+                Some(REGISTERS_REF) => Ok(self.variables(emu)),
+                _ => Ok(json!({ "variables": [] })),
+            },
+            "continue" => {
+                debugger.cancel_step();
+                self.halted = false;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                if debugger.step_over(emu) {
+                    self.halted = false;
+                } else {
+                    self.step(emu, debugger);
+                }
+                Ok(json!({}))
+            }
+            "stepIn" => {
+                self.step(emu, debugger);
+                Ok(json!({}))
+            }
+            "stepOut" => {
+                if debugger.step_out(emu) {
+                    self.halted = false;
+                } else {
+                    self.step(emu, debugger);
+                }
+                Ok(json!({}))
+            }
+            "pause" => {
+                debugger.cancel_step();
+                self.stopped("pause", None);
+                Ok(json!({}))
+            }
+            "readMemory" => self.read_memory(arguments, emu),
+            "disassemble" => self.disassemble(arguments, emu),
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json!({})));
+                self.connected = false;
+                self.halted = false;
+                return ClientAction::Quit;
+            }
+            command => Err(format!("{} is not supported", command)),
+        };
+        self.respond(request, result);
+        ClientAction::Continue
+    }
+}
+
+impl DebugClient for DapServer {
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // while halted this waits a little for more requests so the editor
+    // isn't held up by the frame rate
+    fn poll(&mut self, emu: &mut Chip8, debugger: &mut Debugger) -> ClientAction {
+        if !self.connected {
+            return ClientAction::Continue;
+        }
+        loop {
+            let request = if self.halted {
+                match self.messages.recv_timeout(Duration::from_millis(10)) {
+                    Ok(request) => request,
+                    Err(RecvTimeoutError::Timeout) => return ClientAction::Continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            } else {
+                match self.messages.try_recv() {
+                    Ok(request) => request,
+                    Err(TryRecvError::Empty) => return ClientAction::Continue,
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            };
+            if let ClientAction::Quit = self.handle_request(&request, emu, debugger) {
+                return ClientAction::Quit;
+            }
+        }
+
+        // the editor went away without disconnecting
+        self.connected = false;
+        self.halted = false;
+        ClientAction::Quit
+    }
+
+    fn report_stop(&mut self, reason: &StopReason) {
+        let (reason, text) = DapServer::stop_body(reason);
+        self.stopped(reason, text);
+        self.send_pending();
+    }
+
+    fn report_error(&mut self, error: &Chip8Error) {
+        self.stopped("exception", Some(error.to_string()));
+        self.send_pending();
+    }
+}
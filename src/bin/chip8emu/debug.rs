@@ -0,0 +1,143 @@
+use chip8::core::{Chip8, Chip8Error, Chip8Mode};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Watchpoint {
+    Memory(u16),
+    Register(u8),
+}
+
+impl Watchpoint {
+    fn value(&self, emu: &Chip8) -> u8 {
+        match *self {
+            Watchpoint::Memory(addr) => emu.get_memory().get(addr as usize).copied().unwrap_or(0),
+            Watchpoint::Register(reg) => emu.get_registers()[reg as usize & 0xF],
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StopReason {
+    Breakpoint(u16),
+    Watchpoint { watch: Watchpoint, old: u8, new: u8 },
+    // a step over or step out finished
+    Stepped,
+}
+
+// where a step over or step out should stop
+#[derive(Debug, Copy, Clone)]
+enum Target {
+    // back at the instruction after the call with the call returned
+    Return { addr: u16, depth: usize },
+    // the stack is shallower than depth
+    Depth(usize),
+}
+
+// breakpoints and watchpoints are checked on every instruction run through
+// clock, so the emulator keeps its normal frame timing while continuing
+#[derive(Default)]
+pub struct Debugger {
+    pub breakpoints: Vec<u16>,
+    pub watchpoints: Vec<Watchpoint>,
+    target: Option<Target>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger::default()
+    }
+
+    // runs one instruction and reports why execution should stop, if it should
+    pub fn clock(&mut self, emu: &mut Chip8) -> Result<Option<StopReason>, Chip8Error> {
+        // a stopped machine has nothing to run
+        if emu.mode == Chip8Mode::Stopped {
+            return Ok(None);
+        }
+        let before: Vec<u8> = self.watchpoints.iter().map(|w| w.value(emu)).collect();
+        let waiting = emu.mode == Chip8Mode::WaitingKey;
+        emu.clock()?;
+        // nothing ran while FX0A kept waiting, so there's nothing to stop for
+        if waiting && emu.mode == Chip8Mode::WaitingKey {
+            return Ok(None);
+        }
+
+        for (watch, old) in self.watchpoints.iter().zip(before) {
+            let new = watch.value(emu);
+            if new != old {
+                self.target = None;
+                return Ok(Some(StopReason::Watchpoint {
+                    watch: *watch,
+                    old,
+                    new,
+                }));
+            }
+        }
+
+        let pc = emu.get_pc();
+        let depth = emu.get_stack().len();
+        let reached = match self.target {
+            Some(Target::Return { addr, depth: d }) => pc == addr && depth <= d,
+            Some(Target::Depth(d)) => depth < d,
+            None => false,
+        };
+        if reached {
+            self.target = None;
+            return Ok(Some(StopReason::Stepped));
+        }
+
+        if self.breakpoints.contains(&pc) {
+            self.target = None;
+            return Ok(Some(StopReason::Breakpoint(pc)));
+        }
+        Ok(None)
+    }
+
+    // if pc is on a 2NNN call, sets clock to stop once it returns and gives
+    // true, otherwise a plain step does the same thing
+    pub fn step_over(&mut self, emu: &Chip8) -> bool {
+        let pc = emu.get_pc() as usize;
+        let is_call = emu.get_memory().get(pc).is_some_and(|b| b >> 4 == 0x2);
+        if is_call {
+            self.target = Some(Target::Return {
+                addr: (pc as u16).wrapping_add(2),
+                depth: emu.get_stack().len(),
+            });
+        }
+        is_call
+    }
+
+    // sets clock to stop once the current subroutine returns, false when
+    // not in one
+    pub fn step_out(&mut self, emu: &Chip8) -> bool {
+        let depth = emu.get_stack().len();
+        if depth > 0 {
+            self.target = Some(Target::Depth(depth));
+        }
+        depth > 0
+    }
+
+    pub fn cancel_step(&mut self) {
+        self.target = None;
+    }
+}
+
+pub enum ClientAction {
+    Continue,
+    Quit,
+}
+
+// a debugger front end outside the emulator, gdb or an editor, that the main
+// loop polls once per frame
+pub trait DebugClient {
+    fn is_connected(&self) -> bool;
+
+    // nothing should run while this is true
+    fn is_halted(&self) -> bool;
+
+    // handles whatever the client has sent since the last call
+    fn poll(&mut self, emu: &mut Chip8, debugger: &mut Debugger) -> ClientAction;
+
+    // execution stopped for reason, the client decides when it resumes
+    fn report_stop(&mut self, reason: &StopReason);
+
+    fn report_error(&mut self, error: &Chip8Error);
+}
@@ -0,0 +1,576 @@
+// GDB remote serial protocol stub. Registers are sent in this order, multi
+// byte values little endian:
+//
+//   v0-vf  8 bits each
+//   i      16 bits
+//   pc     16 bits
+//   sp     8 bits, the stack depth, read only
+//   dt     8 bits
+//   st     8 bits
+//
+// gdb has no chip-8 architecture, the layout is described to it through
+// target.xml instead.
+use crate::debug::{ClientAction, DebugClient, Debugger, StopReason, Watchpoint};
+use chip8::core::{Chip8, Chip8Error};
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+// name and size in bytes of every register in the g packet
+const REGISTERS: [(&str, usize); 21] = [
+    ("v0", 1),
+    ("v1", 1),
+    ("v2", 1),
+    ("v3", 1),
+    ("v4", 1),
+    ("v5", 1),
+    ("v6", 1),
+    ("v7", 1),
+    ("v8", 1),
+    ("v9", 1),
+    ("va", 1),
+    ("vb", 1),
+    ("vc", 1),
+    ("vd", 1),
+    ("ve", 1),
+    ("vf", 1),
+    ("i", 2),
+    ("pc", 2),
+    ("sp", 1),
+    ("dt", 1),
+    ("st", 1),
+];
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+pub struct GdbStub {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    buffer: Vec<u8>,
+    halted: bool,
+}
+
+fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n<feature name=\"org.chip8.core\">\n",
+    );
+    for (name, size) in REGISTERS {
+        let kind = match name {
+            "i" => "data_ptr",
+            "pc" => "code_ptr",
+            _ if size == 1 => "uint8",
+            _ => "uint16",
+        };
+        xml.push_str(&format!(
+            "<reg name=\"{}\" bitsize=\"{}\" type=\"{}\"/>\n",
+            name,
+            size * 8,
+            kind
+        ));
+    }
+    xml.push_str("</feature>\n</target>\n");
+    xml
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_num(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+// "addr,len" as used by m, M and the Z packets
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((parse_num(addr)?, parse_num(len)?))
+}
+
+fn read_register(emu: &Chip8, reg: usize) -> Option<Vec<u8>> {
+    let bytes = match reg {
+        0..=0xF => vec![emu.get_registers()[reg]],
+        16 => emu.get_index().to_le_bytes().to_vec(),
+        17 => emu.get_pc().to_le_bytes().to_vec(),
+        18 => vec![emu.get_stack().len() as u8],
+        19 => vec![emu.get_delay_timer()],
+        20 => vec![emu.get_sound_timer()],
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+fn write_register(emu: &mut Chip8, reg: usize, bytes: &[u8]) -> bool {
+    match (reg, bytes) {
+        (0..=0xF, [value]) => emu.set_register(reg, *value),
+        (16, [lo, hi]) => emu.set_index(u16::from_le_bytes([*lo, *hi])),
+        (17, [lo, hi]) => emu.set_pc(u16::from_le_bytes([*lo, *hi])),
+        // the stack depth can't be changed on its own
+        (18, [_]) => {}
+        (19, [value]) => emu.set_delay_timer(*value),
+        (20, [value]) => emu.set_sound_timer(*value),
+        _ => return false,
+    }
+    true
+}
+
+impl GdbStub {
+    // waits for the first client, the emulator starts halted once it attaches
+    pub fn listen(addr: &str) -> Result<GdbStub, String> {
+        let listener =
+            TcpListener::bind(addr).map_err(|e| format!("could not listen on {}: {}", addr, e))?;
+        println!("waiting for gdb on {}", addr);
+        GdbStub::accept(listener)
+    }
+
+    fn accept(listener: TcpListener) -> Result<GdbStub, String> {
+        let (stream, peer) = listener.accept().map_err(|e| e.to_string())?;
+        println!("gdb connected from {}", peer);
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        Ok(GdbStub {
+            listener,
+            stream: Some(stream),
+            buffer: Vec::new(),
+            halted: true,
+        })
+    }
+
+    // reads what is available, None once the client has gone away
+    fn receive(&mut self) -> Option<usize> {
+        let stream = self.stream.as_mut()?;
+        if self.halted {
+            let _ = stream.set_nonblocking(false);
+            let _ = stream.set_read_timeout(Some(Duration::from_millis(10)));
+        } else {
+            let _ = stream.set_nonblocking(true);
+        }
+
+        let mut data = [0; 4096];
+        match stream.read(&mut data) {
+            Ok(0) => {
+                self.disconnect();
+                None
+            }
+            Ok(len) => {
+                self.buffer.extend_from_slice(&data[..len]);
+                Some(len)
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                Some(0)
+            }
+            Err(_) => {
+                self.disconnect();
+                None
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        println!("gdb disconnected");
+        self.stream = None;
+        self.buffer.clear();
+        self.halted = false;
+    }
+
+    // pulls the next complete packet out of the buffer, acknowledging it.
+    // a lone 0x03 is the client asking to interrupt
+    fn next_packet(&mut self) -> Option<String> {
+        loop {
+            match self.buffer.first()? {
+                b'$' => break,
+                0x03 => {
+                    self.buffer.remove(0);
+                    return Some("\x03".to_string());
+                }
+                _ => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+
+        let end = self.buffer.iter().position(|b| *b == b'#')?;
+        if self.buffer.len() < end + 3 {
+            return None;
+        }
+        let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+        let body = &packet[1..end];
+        let checksum = std::str::from_utf8(&packet[end + 1..])
+            .ok()
+            .and_then(|c| u8::from_str_radix(c, 16).ok());
+        let valid = checksum == Some(body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)));
+        self.write_raw(if valid { b"+" } else { b"-" });
+        if !valid {
+            return self.next_packet();
+        }
+        Some(String::from_utf8_lossy(body).into_owned())
+    }
+
+    fn send(&mut self, reply: &str) {
+        let mut body = Vec::new();
+        for byte in reply.bytes() {
+            if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+                body.push(b'}');
+                body.push(byte ^ 0x20);
+            } else {
+                body.push(byte);
+            }
+        }
+        let checksum = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+
+        let mut packet = vec![b'$'];
+        packet.extend_from_slice(&body);
+        packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+        self.write_raw(&packet);
+    }
+
+    fn write_raw(&mut self, data: &[u8]) {
+        if let Some(stream) = &mut self.stream {
+            let _ = stream.set_nonblocking(false);
+            if stream.write_all(data).is_err() {
+                self.disconnect();
+            }
+        }
+    }
+
+    fn handle_packet(
+        &mut self,
+        packet: &str,
+        emu: &mut Chip8,
+        debugger: &mut Debugger,
+    ) -> ClientAction {
+        let (command, params) = match packet.char_indices().nth(1) {
+            Some((index, _)) => packet.split_at(index),
+            None => (packet, ""),
+        };
+        let reply = match command {
+            "\x03" => {
+                self.halted = true;
+                format!("S{:02x}", SIGINT)
+            }
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => (0..REGISTERS.len())
+                .filter_map(|reg| read_register(emu, reg))
+                .map(|bytes| to_hex(&bytes))
+                .collect(),
+            "G" => match from_hex(params) {
+                Some(data) => {
+                    let mut pos = 0;
+                    for (reg, (_, size)) in REGISTERS.iter().enumerate() {
+                        if let Some(bytes) = data.get(pos..pos + size) {
+                            write_register(emu, reg, bytes);
+                        }
+                        pos += size;
+                    }
+                    "OK".to_string()
+                }
+                None => "E01".to_string(),
+            },
+            "p" => match parse_num(params).and_then(|reg| read_register(emu, reg)) {
+                Some(bytes) => to_hex(&bytes),
+                None => "E01".to_string(),
+            },
+            "P" => {
+                let written = params.split_once('=').and_then(|(reg, value)| {
+                    Some(write_register(emu, parse_num(reg)?, &from_hex(value)?))
+                });
+                if written == Some(true) {
+                    "OK".to_string()
+                } else {
+                    "E01".to_string()
+                }
+            }
+            "m" => match parse_range(params) {
+                Some((addr, len)) => match addr
+                    .checked_add(len)
+                    .and_then(|end| emu.get_memory().get(addr..end))
+                {
+                    Some(bytes) => to_hex(bytes),
+                    None => "E01".to_string(),
+                },
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let written = params.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = parse_range(range)?;
+                    let data = from_hex(data)?;
+                    if data.len() != len || addr > u16::MAX as usize {
+                        return None;
+                    }
+                    emu.write_memory(addr as u16, &data).ok()
+                });
+                match written {
+                    Some(()) => "OK".to_string(),
+                    None => "E01".to_string(),
+                }
+            }
+            "Z" | "z" => self.set_breakpoint(command == "Z", params, emu, debugger),
+            "s" => {
+                debugger.cancel_step();
+                match debugger.clock(emu) {
+                    Ok(Some(StopReason::Watchpoint {
+                        watch: Watchpoint::Memory(addr),
+                        ..
+                    })) => format!("T{:02x}watch:{:x};", SIGTRAP, addr),
+                    Ok(_) => format!("S{:02x}", SIGTRAP),
+                    Err(_) => format!("S{:02x}", SIGILL),
+                }
+            }
+            "c" => {
+                debugger.cancel_step();
+                self.halted = false;
+                return ClientAction::Continue;
+            }
+            "D" => {
+                self.send("OK");
+                self.disconnect();
+                return ClientAction::Continue;
+            }
+            "k" => {
+                self.disconnect();
+                return ClientAction::Quit;
+            }
+            "H" => "OK".to_string(),
+            "q" => self.query(params),
+            // unsupported, which tells gdb to fall back to something else
+            _ => String::new(),
+        };
+        self.send(&reply);
+        ClientAction::Continue
+    }
+
+    // Z0 software breakpoints and Z2 write watchpoints
+    fn set_breakpoint(
+        &mut self,
+        insert: bool,
+        params: &str,
+        emu: &Chip8,
+        debugger: &mut Debugger,
+    ) -> String {
+        let (kind, range) = match params.split_once(',') {
+            Some(parts) => parts,
+            None => return "E01".to_string(),
+        };
+        let (addr, len) = match parse_range(range) {
+            Some((addr, len)) if addr <= u16::MAX as usize => (addr as u16, len),
+            _ => return "E01".to_string(),
+        };
+
+        match kind {
+            "0" => {
+                debugger.breakpoints.retain(|b| *b != addr);
+                if insert {
+                    debugger.breakpoints.push(addr);
+                }
+            }
+            // one watchpoint per byte, all of them inside memory
+            "2" => {
+                let end = match (addr as usize).checked_add(len) {
+                    Some(end) if len > 0 && end <= emu.get_memory().len() => end,
+                    _ => return "E01".to_string(),
+                };
+                let range = addr as usize..end;
+                debugger.watchpoints.retain(|w| match w {
+                    Watchpoint::Memory(a) => !range.contains(&(*a as usize)),
+                    Watchpoint::Register(_) => true,
+                });
+                if insert {
+                    let watches = range.map(|a| Watchpoint::Memory(a as u16));
+                    debugger.watchpoints.extend(watches);
+                }
+            }
+            _ => return String::new(),
+        }
+        "OK".to_string()
+    }
+
+    fn query(&mut self, params: &str) -> String {
+        if params.starts_with("Supported") {
+            return "PacketSize=1000;qXfer:features:read+;swbreak+".to_string();
+        }
+        if let Some(request) = params.strip_prefix("Xfer:features:read:target.xml:") {
+            let xml = target_xml();
+            return match parse_range(request) {
+                Some((offset, len)) if offset <= xml.len() => {
+                    let end = offset.saturating_add(len).min(xml.len());
+                    let more = if end < xml.len() { "m" } else { "l" };
+                    format!("{}{}", more, &xml[offset..end])
+                }
+                _ => "E01".to_string(),
+            };
+        }
+        match params {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl DebugClient for GdbStub {
+    fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // while halted this keeps reading until the client goes quiet
+    fn poll(&mut self, emu: &mut Chip8, debugger: &mut Debugger) -> ClientAction {
+        if self.stream.is_none() {
+            if let Ok((stream, peer)) = self.listener.accept() {
+                println!("gdb connected from {}", peer);
+                self.stream = Some(stream);
+                self.halted = true;
+            }
+        }
+
+        loop {
+            let received = match self.receive() {
+                Some(r) => r,
+                None => return ClientAction::Continue,
+            };
+            while let Some(packet) = self.next_packet() {
+                if let ClientAction::Quit = self.handle_packet(&packet, emu, debugger) {
+                    return ClientAction::Quit;
+                }
+            }
+            if !self.halted || received == 0 {
+                return ClientAction::Continue;
+            }
+        }
+    }
+
+    fn report_stop(&mut self, reason: &StopReason) {
+        self.halted = true;
+        let reply = match reason {
+            StopReason::Breakpoint(_) => format!("T{:02x}swbreak:;", SIGTRAP),
+            StopReason::Watchpoint {
+                watch: Watchpoint::Memory(addr),
+                ..
+            } => format!("T{:02x}watch:{:x};", SIGTRAP, addr),
+            _ => format!("S{:02x}", SIGTRAP),
+        };
+        self.send(&reply);
+    }
+
+    fn report_error(&mut self, error: &Chip8Error) {
+        println!("{}, halted for gdb", error);
+        self.halted = true;
+        self.send(&format!("S{:02x}", SIGILL));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::core::Chip8Mode;
+    use std::thread;
+
+    // sends each packet and collects the reply to it
+    fn script(addr: std::net::SocketAddr, packets: &[&str]) -> Vec<String> {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut replies = Vec::new();
+        for packet in packets {
+            let checksum = packet.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+            write!(stream, "${}#{:02x}", packet, checksum).unwrap();
+            if *packet == "k" {
+                break;
+            }
+
+            let mut data = Vec::new();
+            let mut byte = [0];
+            loop {
+                stream.read_exact(&mut byte).unwrap();
+                data.push(byte[0]);
+                let end = data.iter().position(|b| *b == b'#');
+                if end.is_some_and(|end| data.len() == end + 3) {
+                    break;
+                }
+            }
+            let start = data.iter().position(|b| *b == b'$').unwrap();
+            let end = data.iter().position(|b| *b == b'#').unwrap();
+            assert_eq!(data[0], b'+');
+            replies.push(String::from_utf8(data[start + 1..end].to_vec()).unwrap());
+        }
+        replies
+    }
+
+    #[test]
+    fn scripted_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            script(
+                addr,
+                &[
+                    "m200,4",
+                    "mffffffffffffffff,1",
+                    "m1000,1",
+                    "M300,2:abcd",
+                    "m300,2",
+                    "P11=0003",
+                    "p11",
+                    "s",
+                    "p11",
+                    "p0",
+                    "Z0,302,2",
+                    "Z2,400,0",
+                    "Z2,ffe,ffffffffffffffff",
+                    "Z2,ffe,4",
+                    "Z2,ffc,4",
+                    "z2,ffd,2",
+                    "qXfer:features:read:target.xml:0,ffffffffffffffff",
+                    "k",
+                ],
+            )
+        });
+
+        let mut stub = GdbStub::accept(listener).unwrap();
+        let mut emu = Chip8::new();
+        emu.load_rom_bytes(&[0x60, 0x07, 0x12, 0x00], 0x200)
+            .unwrap();
+        emu.mode = Chip8Mode::Running;
+        let mut debugger = Debugger::new();
+        while let ClientAction::Continue = stub.poll(&mut emu, &mut debugger) {}
+
+        let replies = client.join().unwrap();
+        assert_eq!(replies[0], "60071200");
+        assert_eq!(replies[1], "E01");
+        assert_eq!(replies[2], "E01");
+        assert_eq!(replies[3], "OK");
+        assert_eq!(replies[4], "abcd");
+        assert_eq!(replies[5], "OK");
+        assert_eq!(replies[6], "0003");
+        // ABCD at 0x300 sets I and moves on
+        assert_eq!(replies[7], "S05");
+        assert_eq!(replies[8], "0203");
+        assert_eq!(replies[9], "00");
+        assert_eq!(replies[10], "OK");
+        assert_eq!(replies[11..16], ["E01", "E01", "E01", "OK", "OK"]);
+        assert!(replies[16].starts_with("l<?xml"));
+        assert_eq!(debugger.breakpoints, vec![0x302]);
+        assert_eq!(
+            debugger.watchpoints,
+            [Watchpoint::Memory(0xFFC), Watchpoint::Memory(0xFFF)]
+        );
+        assert_eq!(emu.get_index(), 0xBCD);
+    }
+}
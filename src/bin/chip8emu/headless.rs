@@ -0,0 +1,212 @@
+use crate::debug::{ClientAction, Debugger, StopReason};
+use crate::movie::{MoviePlayer, MovieRecorder};
+use crate::options::key_events;
+use crate::repl::{self, ReplAction};
+use crate::{
+    client_halted, connect_client, create_emulator, debug_clock, Args, Palette, DEFAULT_PALETTE,
+};
+use chip8::core::input::KeyEvent;
+use chip8::core::{Chip8, Chip8Mode};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+pub fn run(args: &Args) -> Result<(), String> {
+    let limited =
+        args.machine.frames.is_some() || args.machine.cycles.is_some() || args.play.is_some();
+    if !limited && args.gdb.is_none() && args.dap.is_none() {
+        return Err("headless mode needs --frames, --cycles, --play, --gdb or --dap".to_string());
+    }
+
+    let mut emu = create_emulator(args)?;
+    let mut frame: u64 = 0;
+    let mut cycles: u64 = 0;
+    let mut error = None;
+    let mut recorder = args.record.as_deref().map(MovieRecorder::new);
+    let mut player = match &args.play {
+        Some(path) => Some(MoviePlayer::load(path)?),
+        None => None,
+    };
+    let mut debugger = Debugger::new();
+    let mut client = connect_client(args)?;
+    if args.debug {
+        repl::describe_stop(&emu, &StopReason::Stepped);
+        if let ReplAction::Quit = repl::run(&mut debugger, &mut emu) {
+            return Ok(());
+        }
+    }
+
+    'running: while args.machine.frames.is_none_or(|max| frame < max) {
+        // a movie only holds whole frames, so a recording runs on to the end
+        // of the frame that reaches --cycles
+        if recorder.is_some() && args.machine.cycles.is_some_and(|max| cycles >= max) {
+            break;
+        }
+        // frames only move on once the debug client lets the emulator run
+        if let Some(client) = &mut client {
+            loop {
+                if let ClientAction::Quit = client.poll(&mut emu, &mut debugger) {
+                    break 'running;
+                }
+                if !client.is_halted() {
+                    break;
+                }
+            }
+        }
+
+        match &mut player {
+            // playback ends the run when the movie does
+            Some(movie) => {
+                if !movie.start_frame(&mut emu) {
+                    break;
+                }
+            }
+            None => {
+                let cycle = emu.get_cycles();
+                for (key, down) in key_events(&args.machine.presses, frame) {
+                    emu.queue_key(KeyEvent { cycle, key, down });
+                }
+            }
+        }
+        if let Some(movie) = &mut recorder {
+            movie.start_frame(&mut emu);
+        }
+
+        for _ in 0..args.machine.speed().instructions(frame) {
+            if recorder.is_none() && args.machine.cycles.is_some_and(|max| cycles >= max) {
+                break 'running;
+            }
+            if emu.mode != Chip8Mode::Stopped && !client_halted(&client) {
+                match debug_clock(&mut emu, &mut debugger, &mut client) {
+                    Ok(true) => {}
+                    Ok(false) => break 'running,
+                    Err(e) => {
+                        error = Some(e);
+                        break 'running;
+                    }
+                }
+            }
+            cycles += 1;
+        }
+
+        if let Some(movie) = &mut player {
+            movie.end_frame(&mut emu);
+        }
+        if let Some(movie) = &mut recorder {
+            movie.end_frame(&mut emu);
+        }
+        emu.signal_new_frame();
+        frame += 1;
+
+        if emu.mode == Chip8Mode::Stopped {
+            break;
+        }
+    }
+
+    // stdout carries the protocol when the debug adapter is on stdio
+    let mut out: Box<dyn Write> = match args.dap.as_deref() {
+        Some("stdio") => Box::new(io::stderr()),
+        _ => Box::new(io::stdout()),
+    };
+    match &args.dump {
+        Some(path) if path.ends_with(".pbm") => write_pbm(&emu, path)?,
+        Some(path) if path.ends_with(".png") => {
+            write_png(&emu, &args.palette.unwrap_or(DEFAULT_PALETTE), path)?
+        }
+        Some(path) => return Err(format!("{} is not a .pbm or .png file", path)),
+        None => print_screen(&emu, &mut out).map_err(|e| e.to_string())?,
+    }
+    print_registers(&emu, frame, cycles, &mut out).map_err(|e| e.to_string())?;
+
+    if let Some(movie) = &mut recorder {
+        movie.end_frame(&mut emu);
+        movie.save(&emu)?;
+    }
+    if let Some(e) = error {
+        return Err(e.to_string());
+    }
+    if let Some(movie) = &player {
+        writeln!(out, "{}", movie.report(&emu)?).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn print_screen(emu: &Chip8, out: &mut dyn Write) -> io::Result<()> {
+    let (width, height) = emu.get_resolution();
+    for row in &emu.get_pixels()[..height] {
+        let line: String = row[..width]
+            .iter()
+            .map(|pixel| match pixel & 0x3 {
+                0 => '.',
+                1 => '#',
+                2 => '+',
+                _ => '@',
+            })
+            .collect();
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn print_registers(emu: &Chip8, frame: u64, cycles: u64, out: &mut dyn Write) -> io::Result<()> {
+    let v = emu.get_registers();
+    for (index, value) in v.iter().enumerate() {
+        write!(
+            out,
+            "V{:X}: {:02X}{}",
+            index,
+            value,
+            if index % 8 == 7 { "\n" } else { "  " }
+        )?;
+    }
+    writeln!(
+        out,
+        "PC: {:04X}  I: {:04X}  DT: {:02X}  ST: {:02X}",
+        emu.get_pc(),
+        emu.get_index(),
+        emu.get_delay_timer(),
+        emu.get_sound_timer()
+    )?;
+    let stack: Vec<String> = emu
+        .get_stack()
+        .iter()
+        .map(|addr| format!("{:04X}", addr))
+        .collect();
+    writeln!(out, "stack: [{}]", stack.join(", "))?;
+    writeln!(out, "frames: {}  cycles: {}", frame, cycles)
+}
+
+fn write_pbm(emu: &Chip8, path: &str) -> Result<(), String> {
+    let (width, height) = emu.get_resolution();
+    let mut out = String::new();
+    out.push_str(&format!("P1\n{} {}\n", width, height));
+    for row in &emu.get_pixels()[..height] {
+        let line: Vec<&str> = row[..width]
+            .iter()
+            .map(|pixel| if *pixel != 0 { "1" } else { "0" })
+            .collect();
+        out.push_str(&line.join(" "));
+        out.push('\n');
+    }
+
+    let mut file = File::create(path).map_err(|e| e.to_string())?;
+    file.write_all(out.as_bytes()).map_err(|e| e.to_string())
+}
+
+// in the colours the window would show, --palette or the rom database's
+fn write_png(emu: &Chip8, palette: &Palette, path: &str) -> Result<(), String> {
+    let (width, height) = emu.get_resolution();
+    let mut data = Vec::with_capacity(width * height * 3);
+    for row in &emu.get_pixels()[..height] {
+        for pixel in &row[..width] {
+            let colour = palette.0[*pixel as usize & 0x3];
+            data.extend([colour.r, colour.g, colour.b]);
+        }
+    }
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&data).map_err(|e| e.to_string())
+}
@@ -1,35 +1,301 @@
-#![allow(unused_variables)]
 extern crate sdl2;
 
-use crate::chip8::{Chip8, Chip8Error, Chip8Mode};
+use crate::dap::DapServer;
+use crate::debug::{ClientAction, DebugClient, Debugger, StopReason};
+use crate::gdb::GdbStub;
+use crate::movie::{MoviePlayer, MovieRecorder};
+use crate::options::{read_rom, Machine};
+use crate::repl::ReplAction;
+use crate::rewind::Rewind;
+use crate::scheduler::Scheduler;
+use chip8::core::audio::{AudioState, SampleProducer};
+use chip8::core::input::KeyEvent;
+use chip8::core::{Chip8, Chip8Error, Chip8Mode, FaultAction, Tracer};
 use clap::Parser;
-use sdl2::{event::Event, keyboard::Keycode, keyboard::Scancode, pixels::Color, rect::Rect};
-use std::{fs::File, io::Read, time::Duration};
-mod chip8;
+use sdl2::{
+    audio::{AudioCallback, AudioSpecDesired},
+    event::Event,
+    keyboard::Keycode,
+    keyboard::Scancode,
+    pixels::Color,
+    rect::Rect,
+};
+use std::fs;
+use std::time::Duration;
+mod dap;
+mod debug;
+mod gdb;
+mod headless;
+mod movie;
+mod options;
+mod repl;
+mod rewind;
+mod romdb;
+mod scheduler;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 struct Args {
-    // Path to Rom to load into emulator
-    #[arg(value_name = "rom")]
-    filename: String,
+    #[command(flatten)]
+    machine: Machine,
 
     #[arg(short, long, value_name = "real pixels", default_value_t = 15)]
     pixel_width: u32,
+
+    // Colours for the background, plane 1, plane 2 and both planes as RRGGBB
+    // defaults to the rom database's or 000000,FFFFFF,AAAAAA,555555
+    #[arg(long, value_name = "colours", value_parser = parse_palette)]
+    palette: Option<Palette>,
+
+    // Sound volume between 0 and 1
+    #[arg(long, value_name = "volume", default_value_t = 0.25)]
+    volume: f32,
+
+    // How far back holding backspace can rewind
+    #[arg(long, value_name = "seconds", default_value_t = 30)]
+    rewind_seconds: usize,
+
+    // Run without a window and print the final state, for scripts and CI
+    #[arg(long)]
+    headless: bool,
+
+    // Headless: write the final screen to a .pbm or .png file instead of
+    // printing it as text
+    #[arg(long, value_name = "file")]
+    dump: Option<String>,
+
+    // Record the keys and random values of every frame to a movie file
+    #[arg(long, value_name = "file", conflicts_with = "play")]
+    record: Option<String>,
+
+    // Play back a movie file instead of reading the keyboard and check that
+    // the run ends on the recorded screen
+    #[arg(long, value_name = "file")]
+    play: Option<String>,
+
+    // Start in the debugger, space also breaks into it while running
+    #[arg(long, conflicts_with_all = ["gdb", "dap"])]
+    debug: bool,
+
+    // Wait for a gdb remote protocol client on this address, e.g.
+    // 127.0.0.1:1234, before running
+    #[arg(long, value_name = "address", conflicts_with = "dap")]
+    gdb: Option<String>,
+
+    // Serve the debug adapter protocol for editors, either on stdio or on a
+    // local address like 127.0.0.1:4711
+    #[arg(long, value_name = "stdio|address")]
+    dap: Option<String>,
+
+    // Write a line of machine state for every instruction run to a file
+    #[arg(long, value_name = "file")]
+    trace: Option<String>,
+
+    // Only trace instructions between two hex addresses, e.g. 200-2FF
+    #[arg(long, value_name = "start-end", requires = "trace", value_parser = parse_trace_range)]
+    trace_range: Option<(u16, u16)>,
+
+    // Only trace opcodes starting with these hex digits, e.g. 1,2,B
+    #[arg(long, value_name = "digits", requires = "trace", value_parser = parse_trace_ops)]
+    trace_ops: Option<u16>,
+
+    // Rom databases to look the rom up in by SHA-1 before user.json and
+    // programs.json in ~/.config/chip8emu, see romdb.rs for their layout
+    #[arg(long = "rom-db", value_name = "file")]
+    rom_dbs: Vec<String>,
+
+    // Don't look the rom up in any rom database
+    #[arg(long)]
+    no_rom_db: bool,
+}
+
+struct Beeper {
+    producer: SampleProducer,
+    state: AudioState,
+}
+
+impl AudioCallback for Beeper {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.producer.fill(&self.state, out);
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Palette([Color; 4]);
+
+const DEFAULT_PALETTE: Palette = Palette([
+    Color::RGB(0x00, 0x00, 0x00),
+    Color::RGB(0xFF, 0xFF, 0xFF),
+    Color::RGB(0xAA, 0xAA, 0xAA),
+    Color::RGB(0x55, 0x55, 0x55),
+]);
+
+fn parse_palette(colours: &str) -> Result<Palette, String> {
+    let mut palette = [Color::RGB(0, 0, 0); 4];
+    let parts: Vec<&str> = colours.split(',').collect();
+    if parts.len() != 4 {
+        return Err("expected 4 comma separated colours".to_string());
+    }
+
+    for (colour, part) in palette.iter_mut().zip(parts) {
+        *colour = parse_colour(part)?;
+    }
+    Ok(Palette(palette))
+}
+
+// RRGGBB with or without a leading #
+fn parse_colour(colour: &str) -> Result<Color, String> {
+    let hex = colour.trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => Ok(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+        _ => Err(format!("invalid colour {}", colour)),
+    }
+}
+
+fn parse_trace_range(range: &str) -> Result<(u16, u16), String> {
+    let parse = |addr: &str| u16::from_str_radix(addr.trim_start_matches("0x"), 16);
+    match range.split_once('-').map(|(s, e)| (parse(s), parse(e))) {
+        Some((Ok(start), Ok(end))) if start <= end => Ok((start, end)),
+        _ => Err("expected a hex address range like 200-2FF".to_string()),
+    }
+}
+
+fn parse_trace_ops(digits: &str) -> Result<u16, String> {
+    let mut classes = 0;
+    for digit in digits.split(',') {
+        match u8::from_str_radix(digit.trim(), 16) {
+            Ok(d) if d < 16 => classes |= 1 << d,
+            _ => return Err(format!("invalid opcode digit {}", digit)),
+        }
+    }
+    Ok(classes)
+}
+
+// F1-F4 save to slots 1-4, F5-F8 load them again
+const SAVE_KEYS: [Keycode; 4] = [Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4];
+const LOAD_KEYS: [Keycode; 4] = [Keycode::F5, Keycode::F6, Keycode::F7, Keycode::F8];
+
+// save states are kept next to the rom
+fn state_path(rom: &str, slot: usize) -> String {
+    format!("{}.state{}", rom, slot)
+}
+
+fn save_state(emu: &Chip8, rom: &str, slot: usize) {
+    let path = state_path(rom, slot);
+    match fs::write(&path, emu.save_state()) {
+        Ok(()) => eprintln!("saved state to {}", path),
+        Err(e) => eprintln!("could not save state to {}: {}", path, e),
+    }
+}
+
+fn load_state(emu: &mut Chip8, rom: &str, slot: usize) {
+    let path = state_path(rom, slot);
+    let data = match fs::read(&path) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("could not read state from {}: {}", path, e);
+            return;
+        }
+    };
+    match emu.load_state(&data) {
+        Ok(()) => eprintln!("loaded state from {}", path),
+        Err(e) => eprintln!("could not load state from {}: {}", path, e),
+    }
+}
+
+fn create_emulator(args: &Args) -> Result<Chip8, String> {
+    let mut emu = options::create_emulator(&args.machine)?;
+    if let Some(path) = &args.trace {
+        match fs::File::create(path) {
+            Ok(file) => {
+                let mut tracer = Tracer::new(Box::new(std::io::BufWriter::new(file)));
+                if let Some((start, end)) = args.trace_range {
+                    tracer.set_range(start, end);
+                }
+                if let Some(classes) = args.trace_ops {
+                    tracer.set_classes(classes);
+                }
+                emu.set_tracer(Some(tracer));
+            }
+            Err(e) => eprintln!("could not create trace file {}: {}", path, e),
+        }
+    }
+    Ok(emu)
+}
+
+// waits for the gdb or debug adapter client asked for on the command line
+fn connect_client(args: &Args) -> Result<Option<Box<dyn DebugClient>>, String> {
+    if let Some(addr) = &args.gdb {
+        return Ok(Some(Box::new(GdbStub::listen(addr)?)));
+    }
+    if let Some(transport) = &args.dap {
+        return Ok(Some(Box::new(DapServer::listen(transport, args)?)));
+    }
+    Ok(None)
+}
+
+// runs one instruction, handing anything that stops it to the debug client
+// when one is attached and to the repl otherwise. false means quit
+fn debug_clock(
+    emu: &mut Chip8,
+    debugger: &mut Debugger,
+    client: &mut Option<Box<dyn DebugClient>>,
+) -> Result<bool, Chip8Error> {
+    let client = client.as_mut().filter(|client| client.is_connected());
+    match (debugger.clock(emu), client) {
+        (Ok(None), _) => Ok(true),
+        (Ok(Some(reason)), Some(client)) => {
+            client.report_stop(&reason);
+            Ok(true)
+        }
+        (Ok(Some(reason)), None) => {
+            repl::describe_stop(emu, &reason);
+            Ok(matches!(repl::run(debugger, emu), ReplAction::Resume))
+        }
+        (Err(e), Some(client)) => {
+            client.report_error(&e);
+            Ok(true)
+        }
+        // a paused fault leaves pc on the instruction for the debugger
+        (Err(e), None) if emu.faults.action(e.kind) == FaultAction::Pause => {
+            println!("{}", e);
+            Ok(matches!(repl::run(debugger, emu), ReplAction::Resume))
+        }
+        (Err(e), None) => Err(e),
+    }
+}
+
+// true while a debug client has execution stopped
+fn client_halted(client: &Option<Box<dyn DebugClient>>) -> bool {
+    client.as_ref().is_some_and(|client| client.is_halted())
 }
 
 pub fn main() -> Result<(), String> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    args.machine.rom = read_rom(&args.machine.filename)?;
+    let entry = if args.no_rom_db {
+        None
+    } else {
+        romdb::lookup(&romdb::paths(&args.rom_dbs), &args.machine.rom)
+    };
+    if let Some(entry) = &entry {
+        entry.apply(&mut args);
+    }
+    if args.headless {
+        return headless::run(&args);
+    }
 
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
+    let title = match &entry {
+        Some(entry) if !entry.title.is_empty() => format!("{} - Chip-8 Emulator", entry.title),
+        _ => "Chip-8 Emulator".to_string(),
+    };
     let window = video_subsystem
-        .window(
-            "Chip-8 Emulator",
-            64 * args.pixel_width,
-            32 * args.pixel_width,
-        )
+        .window(&title, 64 * args.pixel_width, 32 * args.pixel_width)
         .position_centered()
         .opengl()
         .build()
@@ -42,8 +308,8 @@ pub fn main() -> Result<(), String> {
     canvas.present();
     let mut event_pump = sdl_context.event_pump()?;
 
-    let mut emu = Chip8::new();
-    let keybinds: [Scancode; 0x10] = [
+    let mut emu = create_emulator(&args)?;
+    let mut keybinds: Vec<(Scancode, u8)> = [
         Scancode::Num0,
         Scancode::Num1,
         Scancode::Num2,
@@ -60,29 +326,50 @@ pub fn main() -> Result<(), String> {
         Scancode::D,
         Scancode::E,
         Scancode::F,
-    ];
-    let _ = emu.load_rom(&args.filename, 0x200);
-
-    let font_data: [u8; 50] = [
-        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-        0x60, 0xA0, 0x20, 0x20, 0xF0, // 1
-        0x60, 0x90, 0x20, 0x40, 0xF0, // 2
-        0xE0, 0x10, 0x60, 0x10, 0xE0, // 3
-        0x90, 0x90, 0x60, 0x10, 0x10, // 4
-        0xF0, 0x80, 0xE0, 0x10, 0xE0, // 5
-        0x70, 0x80, 0xF0, 0x90, 0x60, // 6
-        0xF0, 0x10, 0x20, 0x40, 0x80, // 7
-        0x60, 0x90, 0x60, 0x90, 0x60, // 8
-        0x60, 0x90, 0xF0, 0x10, 0x60, // 9
-    ];
-    emu.load_font(&font_data);
-    emu.mode = Chip8Mode::Running;
+    ]
+    .into_iter()
+    .zip(0..)
+    .collect();
+    // the rom database can put keypad keys on the arrows, z and x as well
+    if let Some(entry) = &entry {
+        keybinds.extend(&entry.keys);
+    }
+    let keypad = |key: Scancode| {
+        keybinds
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, index)| *index)
+    };
 
-    'running: loop {
-        // reset pressed keys
-        let mut pressed = None;
-        let mut step = false;
+    let audio_subsystem = sdl_context.audio()?;
+    let desired_spec = AudioSpecDesired {
+        freq: Some(44100),
+        channels: Some(1),
+        samples: None,
+    };
+    let mut audio_device = audio_subsystem.open_playback(None, &desired_spec, |spec| Beeper {
+        producer: SampleProducer::new(spec.freq as u32, args.volume.clamp(0.0, 1.0)),
+        state: emu.get_audio_state(),
+    })?;
+    audio_device.resume();
+
+    let mut rewind = Rewind::new(args.rewind_seconds * 60);
+    let mut recorder = args.record.as_deref().map(MovieRecorder::new);
+    let mut player = match &args.play {
+        Some(path) => Some(MoviePlayer::load(path)?),
+        None => None,
+    };
 
+    let mut debugger = Debugger::new();
+    let mut break_in = args.debug;
+    let mut client = connect_client(&args)?;
+    let mut scheduler = Scheduler::new(args.machine.speed());
+    let timer = sdl_context.timer()?;
+    // keypad events waiting for a frame, with their sdl timestamps
+    let mut keys: Vec<(u32, u8, bool)> = Vec::new();
+    let palette = args.palette.unwrap_or(DEFAULT_PALETTE);
+
+    'running: loop {
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
@@ -93,59 +380,157 @@ pub fn main() -> Result<(), String> {
                 Event::KeyDown {
                     keycode: Some(Keycode::Space),
                     ..
-                } => step = true,
-                Event::KeyUp { scancode: key, .. } => pressed = key,
+                } => break_in = true,
+                // movie input replaces the keyboard until it runs out
+                Event::KeyDown {
+                    timestamp,
+                    scancode: Some(key),
+                    repeat: false,
+                    ..
+                } if player.is_none() && keypad(key).is_some() => {
+                    keys.push((timestamp, keypad(key).unwrap_or(0), true));
+                }
+                Event::KeyUp {
+                    timestamp,
+                    scancode: Some(key),
+                    ..
+                } if player.is_none() && keypad(key).is_some() => {
+                    keys.push((timestamp, keypad(key).unwrap_or(0), false));
+                }
+                Event::KeyDown {
+                    keycode: Some(key), ..
+                } => {
+                    if let Some(slot) = SAVE_KEYS.iter().position(|k| *k == key) {
+                        save_state(&emu, &args.machine.filename, slot + 1);
+                    } else if let Some(slot) = LOAD_KEYS.iter().position(|k| *k == key) {
+                        // like rewinding, a movie can't follow the jump
+                        if recorder.is_none() && player.is_none() {
+                            load_state(&mut emu, &args.machine.filename, slot + 1);
+                        } else {
+                            eprintln!("states can't be loaded while a movie is running");
+                        }
+                    }
+                }
                 _ => {}
             }
         }
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        // draw emu output
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        for (y, row) in emu.get_pixels().iter().enumerate() {
-            for (x, pixel) in row.iter().enumerate() {
-                if *pixel {
-                    canvas.fill_rect(Rect::new(
-                        x as i32 * args.pixel_width as i32,
-                        y as i32 * args.pixel_width as i32,
-                        args.pixel_width,
-                        args.pixel_width,
-                    ))?;
+        // run whatever frames are due, key events since the last ones go
+        // to the first of them
+        for _ in 0..scheduler.frames_due() {
+            let instructions = scheduler.start_frame();
+
+            // hold backspace to step back through the rewind history, movies
+            // can't follow the jump back so it is off while one is running
+            if recorder.is_none()
+                && player.is_none()
+                && event_pump
+                    .keyboard_state()
+                    .is_scancode_pressed(Scancode::Backspace)
+            {
+                if let Some(state) = rewind.pop() {
+                    if let Err(e) = emu.load_state(state) {
+                        eprintln!("{}", e);
+                    }
                 }
+                audio_device.lock().state.playing = false;
+                continue;
             }
-        }
 
-        canvas.present();
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
-
-        // set pressed key
-        emu.pressed_key = match pressed {
-            None => None,
-            Some(scancode) => match keybinds.iter().position(|x| *x == scancode) {
-                None => None,
-                Some(index) => Some(index as u8),
-            },
-        };
-
-        // set keys that are down
-        for (index, key) in keybinds.iter().enumerate() {
-            emu.down_keys[index] = event_pump.keyboard_state().is_scancode_pressed(*key);
-        }
+            // nothing runs and the timers hold while the debug client has the
+            // emulator halted
+            if let Some(client) = &mut client {
+                if let ClientAction::Quit = client.poll(&mut emu, &mut debugger) {
+                    break 'running;
+                }
+            }
+            if client_halted(&client) {
+                audio_device.lock().state.playing = false;
+                continue;
+            }
+
+            // keys pressed over the last frame's worth of time go in at the
+            // same points of this one
+            let start = emu.get_cycles();
+            let now = timer.ticks();
+            for (timestamp, key, down) in keys.drain(..) {
+                let age = Duration::from_millis(now.wrapping_sub(timestamp) as u64);
+                let cycle = start + scheduler::event_offset(age, instructions);
+                emu.queue_key(KeyEvent { cycle, key, down });
+            }
+
+            if let Some(movie) = &mut player {
+                if !movie.start_frame(&mut emu) {
+                    match movie.report(&emu) {
+                        Ok(msg) | Err(msg) => eprintln!("{}", msg),
+                    }
+                    player = None;
+                }
+            }
+            if let Some(movie) = &mut recorder {
+                movie.start_frame(&mut emu);
+            }
+
+            // the window stops updating while the debugger waits on stdin
+            if break_in {
+                break_in = false;
+                debugger.cancel_step();
+                repl::describe_stop(&emu, &StopReason::Stepped);
+                if let ReplAction::Quit = repl::run(&mut debugger, &mut emu) {
+                    break 'running;
+                }
+            }
 
-        // clock cpu
-        for _ in 0..10 {
-            if emu.mode == Chip8Mode::Running {
-                match emu.clock() {
-                    Ok(()) => {}
-                    Err(e) => {
-                        println!("{:?}", e);
-                        return Ok(());
+            // clock cpu
+            for _ in 0..instructions {
+                if emu.mode != Chip8Mode::Stopped && !client_halted(&client) {
+                    match debug_clock(&mut emu, &mut debugger, &mut client) {
+                        Ok(true) => {}
+                        Ok(false) => break 'running,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            break 'running;
+                        }
                     }
                 }
             }
+
+            if let Some(movie) = &mut player {
+                movie.end_frame(&mut emu);
+            }
+            if let Some(movie) = &mut recorder {
+                movie.end_frame(&mut emu);
+            }
+            emu.signal_new_frame();
+            audio_device.lock().state = emu.get_audio_state();
+            rewind.push(emu.save_state());
+        }
+
+        // let sdl scale the current resolution up to the window size
+        let (width, height) = emu.get_resolution();
+        canvas
+            .set_logical_size(width as u32, height as u32)
+            .map_err(|e| e.to_string())?;
+
+        canvas.set_draw_color(palette.0[0]);
+        canvas.clear();
+        // draw emu output, coloured by which planes are set
+        for (y, row) in emu.get_pixels()[..height].iter().enumerate() {
+            for (x, pixel) in row[..width].iter().enumerate() {
+                if *pixel != 0 {
+                    canvas.set_draw_color(palette.0[*pixel as usize & 0x3]);
+                    canvas.fill_rect(Rect::new(x as i32, y as i32, 1, 1))?;
+                }
+            }
         }
-        emu.signal_new_frame();
+
+        canvas.present();
+        scheduler.wait();
+    }
+
+    if let Some(movie) = &mut recorder {
+        movie.end_frame(&mut emu);
+        movie.save(&emu)?;
     }
 
     Ok(())
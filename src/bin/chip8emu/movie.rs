@@ -0,0 +1,256 @@
+// Movie file format, all multi-byte values are little endian:
+//
+//   magic          4 bytes  "C8MV"
+//   version        u16      MOVIE_VERSION
+//   frame count    u32
+//   frames, each:
+//     key events   u8 count followed by that many events, each a u64 cycle
+//                  then a byte with the key in the low nibble and bit 7 set
+//                  for key down
+//     randoms      u16 count followed by that many CXNN values
+//   final hash     u64, framebuffer_hash() once the last frame has run
+//
+// Version 1 stored the keys that were down in each frame as a u16 followed
+// by a u8 key released in the frame, 0xFF if none. Those are turned into the
+// events that change one frame's keys into the next on load. Version 2 events
+// have no cycle. Events without one happen at the start of their frame.
+//
+// A movie starts from power on, so it has to be played back with the same
+// rom, platform and quirks it was recorded with.
+use chip8::core::input::KeyEvent;
+use chip8::core::Chip8;
+use std::{collections::VecDeque, fs};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+const MOVIE_VERSION: u16 = 3;
+
+struct MovieFrame {
+    // key, whether it went down and the cycle it did, if known
+    keys: Vec<(u8, bool, Option<u64>)>,
+    randoms: Vec<u8>,
+}
+
+// fnv-1a over the resolution and every pixel
+pub fn framebuffer_hash(emu: &Chip8) -> u64 {
+    let (width, height) = emu.get_resolution();
+    let mut hash: u64 = 0xCBF29CE484222325;
+    let mut feed = |byte: u8| {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001B3);
+    };
+    feed(width as u8);
+    feed(height as u8);
+    for row in emu.get_pixels() {
+        for pixel in row {
+            feed(*pixel);
+        }
+    }
+    hash
+}
+
+pub struct MovieRecorder {
+    path: String,
+    frames: Vec<MovieFrame>,
+}
+
+impl MovieRecorder {
+    pub fn new(path: &str) -> MovieRecorder {
+        MovieRecorder {
+            path: path.to_string(),
+            frames: Vec::new(),
+        }
+    }
+
+    // call before running the frame
+    pub fn start_frame(&mut self, emu: &mut Chip8) {
+        self.frames.push(MovieFrame {
+            keys: Vec::new(),
+            randoms: Vec::new(),
+        });
+        emu.random_log = Some(Vec::new());
+        emu.key_log = Some(Vec::new());
+    }
+
+    pub fn end_frame(&mut self, emu: &mut Chip8) {
+        if let Some(frame) = self.frames.last_mut() {
+            if let Some(randoms) = emu.random_log.take() {
+                frame.randoms = randoms;
+            }
+            if let Some(keys) = emu.key_log.take() {
+                frame.keys = keys
+                    .iter()
+                    .map(|event| (event.key, event.down, Some(event.cycle)))
+                    .collect();
+            }
+        }
+    }
+
+    pub fn save(&self, emu: &Chip8) -> Result<(), String> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for frame in &self.frames {
+            if frame.keys.len() > u8::MAX as usize {
+                return Err("too many key events in one frame".to_string());
+            }
+            out.push(frame.keys.len() as u8);
+            for (key, down, cycle) in &frame.keys {
+                out.extend_from_slice(&cycle.unwrap_or(0).to_le_bytes());
+                out.push(key | (*down as u8) << 7);
+            }
+            if frame.randoms.len() > u16::MAX as usize {
+                return Err("too many random values in one frame".to_string());
+            }
+            out.extend_from_slice(&(frame.randoms.len() as u16).to_le_bytes());
+            out.extend_from_slice(&frame.randoms);
+        }
+        out.extend_from_slice(&framebuffer_hash(emu).to_le_bytes());
+
+        fs::write(&self.path, out).map_err(|e| format!("could not write {}: {}", self.path, e))
+    }
+}
+
+pub struct MoviePlayer {
+    frames: VecDeque<MovieFrame>,
+    final_hash: u64,
+    frame: usize,
+    // random values recorded for the frame being played
+    randoms: usize,
+    // first frame where the rom used a different number of random values
+    random_mismatch: Option<usize>,
+}
+
+fn read_bytes<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+    if *pos + len > data.len() {
+        return Err("movie file is truncated".to_string());
+    }
+    let bytes = &data[*pos..*pos + len];
+    *pos += len;
+    Ok(bytes)
+}
+
+impl MoviePlayer {
+    pub fn load(path: &str) -> Result<MoviePlayer, String> {
+        let data = fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        let mut pos = 0;
+        if read_bytes(&data, &mut pos, 4)? != MAGIC {
+            return Err(format!("{} is not a movie file", path));
+        }
+        let version = read_bytes(&data, &mut pos, 2)?;
+        let version = u16::from_le_bytes([version[0], version[1]]);
+        if version == 0 || version > MOVIE_VERSION {
+            return Err(format!("{} has an unsupported movie version", path));
+        }
+
+        let count = read_bytes(&data, &mut pos, 4)?;
+        let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]);
+        let mut frames = VecDeque::with_capacity(count as usize);
+        let mut down_before: u16 = 0;
+        for _ in 0..count {
+            let keys = if version >= 3 {
+                let len = read_bytes(&data, &mut pos, 1)?[0] as usize;
+                let mut keys = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let mut cycle = [0; 8];
+                    cycle.copy_from_slice(read_bytes(&data, &mut pos, 8)?);
+                    let event = read_bytes(&data, &mut pos, 1)?[0];
+                    keys.push((
+                        event & 0xF,
+                        event & 0x80 != 0,
+                        Some(u64::from_le_bytes(cycle)),
+                    ));
+                }
+                keys
+            } else if version == 2 {
+                let len = read_bytes(&data, &mut pos, 1)?[0] as usize;
+                read_bytes(&data, &mut pos, len)?
+                    .iter()
+                    .map(|event| (event & 0xF, event & 0x80 != 0, None))
+                    .collect()
+            } else {
+                let down = read_bytes(&data, &mut pos, 2)?;
+                let down = u16::from_le_bytes([down[0], down[1]]);
+                // the released key is already up in down_keys
+                read_bytes(&data, &mut pos, 1)?;
+                let changed = down ^ down_before;
+                down_before = down;
+                (0..0x10u8)
+                    .filter(|key| changed & (1 << key) != 0)
+                    .map(|key| (key, down & (1 << key) != 0, None))
+                    .collect()
+            };
+            let len = read_bytes(&data, &mut pos, 2)?;
+            let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+            let randoms = read_bytes(&data, &mut pos, len)?.to_vec();
+            frames.push_back(MovieFrame { keys, randoms });
+        }
+
+        let mut hash = [0; 8];
+        hash.copy_from_slice(read_bytes(&data, &mut pos, 8)?);
+
+        Ok(MoviePlayer {
+            frames,
+            final_hash: u64::from_le_bytes(hash),
+            frame: 0,
+            randoms: 0,
+            random_mismatch: None,
+        })
+    }
+
+    // sets this frame's recorded inputs, returns false once the movie is over
+    pub fn start_frame(&mut self, emu: &mut Chip8) -> bool {
+        let frame = match self.frames.pop_front() {
+            Some(f) => f,
+            None => {
+                emu.random_replay = None;
+                return false;
+            }
+        };
+        let start = emu.get_cycles();
+        for (key, down, cycle) in frame.keys {
+            emu.queue_key(KeyEvent {
+                cycle: cycle.unwrap_or(start),
+                key,
+                down,
+            });
+        }
+        self.randoms = frame.randoms.len();
+        emu.random_replay = Some(VecDeque::from(frame.randoms));
+        // the log counts values drawn after the replay runs out as well, a
+        // recorder starting this frame too swaps in its own empty log
+        emu.random_log = Some(Vec::new());
+        true
+    }
+
+    // call before MovieRecorder::end_frame, which takes the random log
+    pub fn end_frame(&mut self, emu: &mut Chip8) {
+        let used = emu.random_log.as_ref().map_or(0, Vec::len);
+        if used != self.randoms && self.random_mismatch.is_none() {
+            self.random_mismatch = Some(self.frame);
+        }
+        self.frame += 1;
+    }
+
+    // describes whether the run ended up where the recording did
+    pub fn report(&self, emu: &Chip8) -> Result<String, String> {
+        if let Some(frame) = self.random_mismatch {
+            return Err(format!(
+                "movie diverged: frame {} used a different number of random values than recorded",
+                frame
+            ));
+        }
+
+        let hash = framebuffer_hash(emu);
+        if hash != self.final_hash {
+            return Err(format!(
+                "movie diverged: framebuffer hash {:016X} after {} frames, recorded {:016X}",
+                hash, self.frame, self.final_hash
+            ));
+        }
+        Ok(format!(
+            "movie matched after {} frames, framebuffer hash {:016X}",
+            self.frame, hash
+        ))
+    }
+}
@@ -0,0 +1,286 @@
+// The settings for how a rom is run that chip8emu shares with chip8diff,
+// which includes this file as well. Nothing here may need sdl.
+use chip8::core::font::Font;
+use chip8::core::quirks::Quirks;
+use chip8::core::rng::Rng;
+use chip8::core::rom;
+use chip8::core::{
+    Addressing, Chip8, Chip8ErrorKind, Chip8Mode, FaultAction, FaultPolicy, Platform,
+};
+use std::fs;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Machine {
+    // Path to Rom to load into emulator, - for stdin, or a .zip or .gz
+    // holding it
+    #[arg(value_name = "rom")]
+    pub filename: String,
+
+    // Hex address the rom is loaded at and starts running from
+    #[arg(long, value_name = "addr", default_value = "200", value_parser = parse_address)]
+    pub load_address: u16,
+
+    // the rom read from filename, kept since stdin can only be read once
+    #[arg(skip)]
+    pub rom: Vec<u8>,
+
+    // Instruction set to run: chip8, schip or xo-chip
+    // defaults to the rom database's or chip8
+    #[arg(long, value_name = "platform", value_parser = parse_platform)]
+    pub platform: Option<Platform>,
+
+    // Quirks preset: vip, chip48, schip-legacy, schip-modern or xo-chip
+    // defaults to the one matching the platform
+    #[arg(short, long, value_name = "preset", value_parser = parse_quirks)]
+    pub quirks: Option<Quirks>,
+
+    // Font set: vip, dream6800, eti660, schip or octo, or a file with the 80
+    // bytes of small glyphs optionally followed by 10 or 16 big ones.
+    // Defaults to the one matching the platform
+    #[arg(long, value_name = "font", value_parser = parse_font)]
+    pub font: Option<Font>,
+
+    // Hex address the font is loaded at
+    #[arg(long, value_name = "addr", default_value = "0", value_parser = parse_address)]
+    pub font_address: u16,
+
+    // Seed for CXNN so runs can be reproduced, thread random if not given
+    #[arg(long, value_name = "seed")]
+    pub seed: Option<u64>,
+
+    // Generate CXNN values the way the COSMAC VIP interpreter does
+    #[arg(long)]
+    pub vip_rng: bool,
+
+    // Headless and diffs: stop after this many frames
+    #[arg(long, value_name = "frames")]
+    pub frames: Option<u64>,
+
+    // Headless and diffs: stop after this many instructions
+    #[arg(long, value_name = "cycles")]
+    pub cycles: Option<u64>,
+
+    // Headless and diffs: hold a key, written as KEY@FRAME or KEY@FRAME+FRAMES
+    // e.g. a@120+5 holds key A for 5 frames starting at frame 120
+    #[arg(long = "press", value_name = "key press", value_parser = parse_key_press)]
+    pub presses: Vec<KeyPress>,
+
+    // Whether FX0A finishes when a key is pressed or when it is released,
+    // defaults to the quirks preset
+    #[arg(long, value_name = "event", value_parser = parse_key_wait)]
+    pub key_wait: Option<bool>,
+
+    // Addresses past the end of memory either wrap around to the start like
+    // the real hardware, or trap and raise an address error
+    #[arg(long, value_name = "mode", default_value = "trap", value_parser = parse_addressing)]
+    pub addressing: Addressing,
+
+    // What to do when an instruction fails, halt, ignore or pause in the
+    // debugger, for all errors or per kind, e.g. all=pause,address=ignore.
+    // Kinds are instruction, stack-overflow, stack-underflow and address
+    #[arg(long, value_name = "kind=action", default_value = "all=halt", value_parser = parse_faults)]
+    pub faults: FaultPolicy,
+
+    // Instructions run in each 60 Hz frame, defaults to the rom database's
+    // tickrate or 10
+    #[arg(long, value_name = "count", value_parser = clap::value_parser!(u32).range(1..))]
+    pub ipf: Option<u32>,
+
+    // Instructions run each second instead of a fixed count per frame
+    #[arg(long, value_name = "rate", conflicts_with = "ipf", value_parser = clap::value_parser!(u32).range(1..))]
+    pub hz: Option<u32>,
+}
+
+impl Machine {
+    pub fn speed(&self) -> Speed {
+        match self.hz {
+            Some(hz) => Speed::PerSecond(hz),
+            None => Speed::PerFrame(self.ipf.unwrap_or(10)),
+        }
+    }
+}
+
+pub fn create_emulator(args: &Machine) -> Result<Chip8, String> {
+    let mut emu = Chip8::new();
+    let platform = args.platform.unwrap_or(Platform::Chip8);
+    emu.set_platform(platform);
+    emu.quirks = args.quirks.unwrap_or(platform.default_quirks());
+    let font = args.font.unwrap_or(platform.default_font());
+    if let Err(e) = emu.set_font(&font, args.font_address) {
+        return Err(format!("could not load the font: {}", e));
+    }
+    if let Some(press) = args.key_wait {
+        emu.quirks.key_wait_press = press;
+    }
+    emu.faults = args.faults;
+    emu.addressing = args.addressing;
+    if args.vip_rng {
+        emu.set_rng(Rng::vip(args.seed.unwrap_or(0) as u8));
+    } else if let Some(seed) = args.seed {
+        emu.set_rng(Rng::seeded(seed));
+    }
+    if let Err(e) = emu.load_rom_bytes(&args.rom, args.load_address) {
+        return Err(match e.kind {
+            Chip8ErrorKind::RomTooLarge => format!(
+                "{} is {} bytes, only {} fit from {:03X}",
+                args.filename,
+                args.rom.len(),
+                emu.get_memory().len() - args.load_address as usize,
+                args.load_address
+            ),
+            _ => format!("could not load {}: {}", args.filename, e),
+        });
+    }
+    emu.mode = Chip8Mode::Running;
+    Ok(emu)
+}
+
+pub fn read_rom(path: &str) -> Result<Vec<u8>, String> {
+    rom::read_rom(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+// holds key from frame for the given number of frames, then releases it
+#[derive(Debug, Copy, Clone)]
+pub struct KeyPress {
+    key: u8,
+    frame: u64,
+    frames: u64,
+}
+
+pub fn parse_key_press(press: &str) -> Result<KeyPress, String> {
+    let (key, timing) = match press.split_once('@') {
+        Some(parts) => parts,
+        None => return Err("expected KEY@FRAME or KEY@FRAME+FRAMES".to_string()),
+    };
+    let (frame, frames) = match timing.split_once('+') {
+        Some((frame, frames)) => (frame, frames),
+        None => (timing, "1"),
+    };
+
+    let key = match u8::from_str_radix(key, 16) {
+        Ok(k) if k < 0x10 => k,
+        _ => return Err(format!("invalid key {}, expected 0-F", key)),
+    };
+    let frame = frame
+        .parse::<u64>()
+        .map_err(|_| format!("invalid frame {}", frame))?;
+    let frames = frames
+        .parse::<u64>()
+        .map_err(|_| format!("invalid frame count {}", frames))?;
+
+    Ok(KeyPress { key, frame, frames })
+}
+
+// the key events at the start of this frame, as key and whether it went down
+pub fn key_events(presses: &[KeyPress], frame: u64) -> Vec<(u8, bool)> {
+    let mut events = Vec::new();
+    for press in presses {
+        if frame == press.frame {
+            events.push((press.key, true));
+        }
+        if frame == press.frame + press.frames {
+            events.push((press.key, false));
+        }
+    }
+    events
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Speed {
+    PerFrame(u32),
+    PerSecond(u32),
+}
+
+impl Speed {
+    // a per second rate that isn't a multiple of 60 is spread evenly over
+    // the frames of each second
+    pub fn instructions(&self, frame: u64) -> u64 {
+        match *self {
+            Speed::PerFrame(count) => count as u64,
+            Speed::PerSecond(hz) => (frame + 1) * hz as u64 / 60 - frame * hz as u64 / 60,
+        }
+    }
+}
+
+pub fn parse_platform(name: &str) -> Result<Platform, String> {
+    match Platform::from_name(name) {
+        Some(p) => Ok(p),
+        None => Err("unknown platform, expected one of: chip8, schip, xo-chip".to_string()),
+    }
+}
+
+pub fn parse_quirks(name: &str) -> Result<Quirks, String> {
+    match Quirks::from_preset(name) {
+        Some(q) => Ok(q),
+        None => Err(format!(
+            "unknown quirks preset, expected one of: {}",
+            Quirks::PRESET_NAMES.join(", ")
+        )),
+    }
+}
+
+pub fn parse_font(font: &str) -> Result<Font, String> {
+    if let Some(font) = Font::from_name(font) {
+        return Ok(font);
+    }
+    let data = fs::read(font).map_err(|e| {
+        format!(
+            "{} is not one of {} and could not be read: {}",
+            font,
+            Font::NAMES.join(", "),
+            e
+        )
+    })?;
+    Font::from_bytes(&data).ok_or("font files are 80, 180 or 240 bytes long".to_string())
+}
+
+pub fn parse_address(addr: &str) -> Result<u16, String> {
+    u16::from_str_radix(addr.trim_start_matches("0x"), 16)
+        .map_err(|_| "expected a hex address like 50".to_string())
+}
+
+pub fn parse_faults(spec: &str) -> Result<FaultPolicy, String> {
+    let mut faults = FaultPolicy::default();
+    for setting in spec.split(',') {
+        let (kind, action) = match setting.split_once('=') {
+            Some(parts) => parts,
+            None => return Err(format!("expected kind=action, got {}", setting)),
+        };
+        let action = match action {
+            "halt" => FaultAction::Halt,
+            "ignore" => FaultAction::Ignore,
+            "pause" => FaultAction::Pause,
+            _ => {
+                return Err(format!(
+                    "unknown action {}, expected halt, ignore or pause",
+                    action
+                ))
+            }
+        };
+        match kind {
+            "all" => faults = FaultPolicy::all(action),
+            "instruction" => faults.invalid_instruction = action,
+            "stack-overflow" => faults.stack_overflow = action,
+            "stack-underflow" => faults.stack_underflow = action,
+            "address" => faults.address_overflow = action,
+            _ => return Err(format!("unknown error kind {}", kind)),
+        }
+    }
+    Ok(faults)
+}
+
+pub fn parse_key_wait(event: &str) -> Result<bool, String> {
+    match event {
+        "press" => Ok(true),
+        "release" => Ok(false),
+        _ => Err("expected press or release".to_string()),
+    }
+}
+
+pub fn parse_addressing(mode: &str) -> Result<Addressing, String> {
+    match mode {
+        "wrap" => Ok(Addressing::Wrap),
+        "trap" => Ok(Addressing::Trap),
+        _ => Err("unknown addressing mode, expected wrap or trap".to_string()),
+    }
+}
@@ -0,0 +1,256 @@
+use crate::debug::{Debugger, StopReason, Watchpoint};
+use chip8::core::disasm::disassemble;
+use chip8::core::Chip8;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+commands, addresses and values are hex:
+  s, step [count]        run count instructions, 1 if not given
+  n, next                step over a 2NNN call
+  o, out                 run until the current subroutine returns
+  c, continue            leave the debugger and keep running
+  b, break ADDR          stop when pc reaches ADDR
+  del, delete ADDR       remove the breakpoint at ADDR
+  w, watch ADDR|vX       stop when a memory byte or register changes
+  uw, unwatch ADDR|vX    remove a watchpoint
+  i, info                list breakpoints and watchpoints
+  r, regs                print registers, timers and the stack
+  m, mem ADDR [LEN]      print LEN bytes of memory, 0x40 if not given
+  l, list [ADDR] [COUNT] disassemble around pc or from ADDR
+  q, quit                exit the emulator
+an empty line repeats the last command";
+
+pub enum ReplAction {
+    Resume,
+    Quit,
+}
+
+pub fn describe_stop(emu: &Chip8, reason: &StopReason) {
+    match reason {
+        StopReason::Breakpoint(addr) => println!("breakpoint at {:04X}", addr),
+        StopReason::Watchpoint { watch, old, new } => {
+            println!("{} changed {:02X} -> {:02X}", watch_name(watch), old, new)
+        }
+        StopReason::Stepped => {}
+    }
+    print_location(emu);
+}
+
+// reads commands from stdin until one resumes execution or quits
+pub fn run(debugger: &mut Debugger, emu: &mut Chip8) -> ReplAction {
+    let stdin = io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(chip8) ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return ReplAction::Quit,
+            Ok(_) => {}
+        }
+        if line.trim().is_empty() {
+            line = last.clone();
+        } else {
+            last = line.clone();
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, params) = match words.split_first() {
+            Some((command, params)) => (*command, params),
+            None => continue,
+        };
+        match command {
+            "s" | "step" => {
+                let count = match params.first() {
+                    Some(p) => match parse_hex(p) {
+                        Some(n) => n,
+                        None => {
+                            println!("invalid count {}", p);
+                            continue;
+                        }
+                    },
+                    None => 1,
+                };
+                step(debugger, emu, count);
+            }
+            "n" | "next" => {
+                if debugger.step_over(emu) {
+                    return ReplAction::Resume;
+                }
+                step(debugger, emu, 1);
+            }
+            "o" | "out" => {
+                if debugger.step_out(emu) {
+                    return ReplAction::Resume;
+                }
+                println!("not in a subroutine");
+            }
+            "c" | "continue" => return ReplAction::Resume,
+            "b" | "break" => match params.first().and_then(|p| parse_addr(p)) {
+                Some(addr) if !debugger.breakpoints.contains(&addr) => {
+                    debugger.breakpoints.push(addr);
+                    println!("breakpoint at {:04X}", addr);
+                }
+                Some(addr) => println!("already a breakpoint at {:04X}", addr),
+                None => println!("expected an address"),
+            },
+            "del" | "delete" => match params.first().and_then(|p| parse_addr(p)) {
+                Some(addr) => debugger.breakpoints.retain(|b| *b != addr),
+                None => println!("expected an address"),
+            },
+            "w" | "watch" => match params.first().and_then(|p| parse_watch(p)) {
+                Some(watch) if !debugger.watchpoints.contains(&watch) => {
+                    debugger.watchpoints.push(watch);
+                    println!("watching {}", watch_name(&watch));
+                }
+                Some(watch) => println!("already watching {}", watch_name(&watch)),
+                None => println!("expected an address or register"),
+            },
+            "uw" | "unwatch" => match params.first().and_then(|p| parse_watch(p)) {
+                Some(watch) => debugger.watchpoints.retain(|w| *w != watch),
+                None => println!("expected an address or register"),
+            },
+            "i" | "info" => {
+                for addr in &debugger.breakpoints {
+                    println!("breakpoint {:04X}", addr);
+                }
+                for watch in &debugger.watchpoints {
+                    println!("watch {}", watch_name(watch));
+                }
+            }
+            "r" | "regs" => print_registers(emu),
+            "m" | "mem" => {
+                let addr = params.first().and_then(|p| parse_addr(p));
+                let len = params.get(1).and_then(|p| parse_hex(p)).unwrap_or(0x40);
+                match addr {
+                    Some(addr) => print_memory(emu, addr as usize, len),
+                    None => println!("expected an address"),
+                }
+            }
+            "l" | "list" => {
+                let count = params.get(1).and_then(|p| parse_hex(p)).unwrap_or(0x10);
+                match params.first() {
+                    Some(p) => match parse_addr(p) {
+                        Some(addr) => print_disassembly(emu, addr, count),
+                        None => println!("expected an address"),
+                    },
+                    // a few instructions before pc for context
+                    None => print_disassembly(emu, emu.get_pc().saturating_sub(8), count),
+                }
+            }
+            "h" | "help" => println!("{}", HELP),
+            "q" | "quit" => return ReplAction::Quit,
+            _ => println!("unknown command {}, try help", command),
+        }
+    }
+}
+
+fn step(debugger: &mut Debugger, emu: &mut Chip8, count: usize) {
+    for _ in 0..count {
+        match debugger.clock(emu) {
+            Ok(None) => {}
+            Ok(Some(reason)) => {
+                describe_stop(emu, &reason);
+                return;
+            }
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        }
+    }
+    print_location(emu);
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16).ok()
+}
+
+fn parse_addr(text: &str) -> Option<u16> {
+    parse_hex(text).and_then(|addr| u16::try_from(addr).ok())
+}
+
+fn parse_watch(text: &str) -> Option<Watchpoint> {
+    match text.strip_prefix(['v', 'V']) {
+        Some(reg) => match u8::from_str_radix(reg, 16) {
+            Ok(reg) if reg < 0x10 => Some(Watchpoint::Register(reg)),
+            _ => None,
+        },
+        None => parse_addr(text).map(Watchpoint::Memory),
+    }
+}
+
+fn watch_name(watch: &Watchpoint) -> String {
+    match watch {
+        Watchpoint::Memory(addr) => format!("memory {:04X}", addr),
+        Watchpoint::Register(reg) => format!("v{:X}", reg),
+    }
+}
+
+fn print_location(emu: &Chip8) {
+    let pc = emu.get_pc() as usize;
+    let (text, _) = disassemble(emu.get_memory(), pc, emu.get_platform());
+    println!("{:04X}: {}", pc, text);
+}
+
+fn print_registers(emu: &Chip8) {
+    let v = emu.get_registers();
+    for (index, value) in v.iter().enumerate() {
+        print!(
+            "V{:X}: {:02X}{}",
+            index,
+            value,
+            if index % 8 == 7 { "\n" } else { "  " }
+        );
+    }
+    println!(
+        "PC: {:04X}  I: {:04X}  DT: {:02X}  ST: {:02X}  cycles: {}",
+        emu.get_pc(),
+        emu.get_index(),
+        emu.get_delay_timer(),
+        emu.get_sound_timer(),
+        emu.get_cycles()
+    );
+    let stack: Vec<String> = emu
+        .get_stack()
+        .iter()
+        .map(|addr| format!("{:04X}", addr))
+        .collect();
+    println!("stack: [{}]", stack.join(", "));
+}
+
+fn print_memory(emu: &Chip8, addr: usize, len: usize) {
+    let memory = emu.get_memory();
+    let end = addr.saturating_add(len).min(memory.len());
+    for row in (addr..end).step_by(0x10) {
+        let bytes: Vec<String> = memory[row..(row + 0x10).min(end)]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        println!("{:04X}: {}", row, bytes.join(" "));
+    }
+}
+
+fn print_disassembly(emu: &Chip8, addr: u16, count: usize) {
+    let memory = emu.get_memory();
+    let mut addr = addr as usize;
+    for _ in 0..count {
+        if addr >= memory.len() {
+            break;
+        }
+        let (text, len) = disassemble(memory, addr, emu.get_platform());
+        let marker = if addr == emu.get_pc() as usize {
+            "=>"
+        } else {
+            "  "
+        };
+        let bytes: Vec<String> = memory[addr..addr.saturating_add(len).min(memory.len())]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        println!("{} {:04X}: {:<9} {}", marker, addr, bytes.join(""), text);
+        addr += len;
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+
+// Keeps the newest save state in full and every older frame as the xor
+// against the frame after it, run length encoded. Consecutive frames barely
+// differ so most deltas are a handful of bytes.
+pub struct Rewind {
+    capacity: usize,
+    deltas: VecDeque<Vec<u8>>,
+    latest: Option<Vec<u8>>,
+}
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind {
+            capacity,
+            deltas: VecDeque::with_capacity(capacity),
+            latest: None,
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        match &self.latest {
+            Some(latest) if latest.len() == state.len() => {
+                self.deltas.push_back(encode_delta(latest, &state));
+                if self.deltas.len() > self.capacity {
+                    self.deltas.pop_front();
+                }
+            }
+            // the state layout changed, older frames can't be rebuilt from it
+            _ => self.deltas.clear(),
+        }
+        self.latest = Some(state);
+    }
+
+    // steps back one frame and returns that frame's state
+    pub fn pop(&mut self) -> Option<&[u8]> {
+        let delta = self.deltas.pop_back()?;
+        let latest = self.latest.as_mut()?;
+        apply_delta(latest, &delta);
+        Some(latest)
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some(byte) = data.get(*pos) {
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+// encodes old ^ new as pairs of (zero run length, literal run) until the end
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < new.len() {
+        let zeros = old[pos..]
+            .iter()
+            .zip(&new[pos..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        pos += zeros;
+        let literals = old[pos..]
+            .iter()
+            .zip(&new[pos..])
+            .take_while(|(a, b)| a != b)
+            .count();
+
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        for offset in pos..pos + literals {
+            out.push(old[offset] ^ new[offset]);
+        }
+        pos += literals;
+    }
+    out
+}
+
+// xoring the delta back in turns the newer state into the older one
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut read = 0;
+    while read < delta.len() {
+        pos += read_varint(delta, &mut read);
+        let literals = read_varint(delta, &mut read);
+        for byte in &delta[read..read + literals] {
+            state[pos] ^= byte;
+            pos += 1;
+        }
+        read += literals;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(old: &[u8], new: &[u8]) -> Vec<u8> {
+        let delta = encode_delta(old, new);
+        let mut state = new.to_vec();
+        apply_delta(&mut state, &delta);
+        assert_eq!(state, old);
+        delta
+    }
+
+    #[test]
+    fn identical_states_are_one_zero_run() {
+        let state = vec![0x5A; 0x1000];
+        // 0x1000 zeros, no literals
+        assert_eq!(round_trip(&state, &state), vec![0x80, 0x20, 0x00]);
+    }
+
+    #[test]
+    fn fully_different_states_are_one_literal_run() {
+        let old: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let new: Vec<u8> = old.iter().map(|b| !b).collect();
+        let delta = round_trip(&old, &new);
+        assert_eq!(&delta[..3], &[0x00, 0xAC, 0x02]);
+        assert_eq!(delta.len(), 3 + 300);
+    }
+
+    // run lengths have no maximum, past 0x7F they take more varint bytes
+    #[test]
+    fn runs_longer_than_one_varint_byte() {
+        let old = vec![0; 70000];
+        let mut new = old.clone();
+        new[200..20200].fill(1);
+        new[69999] = 2;
+        round_trip(&old, &new);
+        round_trip(&new, &old);
+    }
+
+    #[test]
+    fn oldest_frames_fall_off_the_ring() {
+        let mut rewind = Rewind::new(3);
+        for frame in 0..6u8 {
+            let mut state = vec![0; 0x100];
+            state[frame as usize] = frame + 1;
+            state[0xFF] = frame;
+            rewind.push(state);
+        }
+        for frame in (2..5u8).rev() {
+            let state = rewind.pop().unwrap().to_vec();
+            assert_eq!(state[frame as usize], frame + 1);
+            assert_eq!(state[0xFF], frame);
+            assert_eq!(state.iter().filter(|b| **b != 0).count(), 2);
+        }
+        assert!(rewind.pop().is_none());
+    }
+
+    #[test]
+    fn a_new_layout_drops_older_frames() {
+        let mut rewind = Rewind::new(3);
+        rewind.push(vec![1; 0x10]);
+        rewind.push(vec![2; 0x20]);
+        assert!(rewind.pop().is_none());
+    }
+}
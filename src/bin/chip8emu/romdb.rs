@@ -0,0 +1,196 @@
+// Roms are looked up by the SHA-1 of their bytes in files laid out like
+// programs.json from the community CHIP-8 database: a list of programs, each
+// with a title and its roms keyed by hash. A rom lists the platforms it runs
+// on best first, with the quirks it needs that differ from a platform's own
+// under quirkyPlatforms, and optionally a tickrate, the keypad keys behind
+// its up, down, left, right, a and b controls and the colours it was made
+// for. Your own entries go in a file of the same shape, either passed with
+// --rom-db or kept as user.json next to programs.json in the config
+// directory, and are found before the community ones.
+use crate::{parse_colour, Args, DEFAULT_PALETTE};
+use chip8::core::quirks::{IndexIncrement, Quirks};
+use chip8::core::Platform;
+use sdl2::{keyboard::Scancode, pixels::Color};
+use serde_json::Value;
+use sha1_smol::Sha1;
+use std::{env, fs, path::PathBuf};
+
+// the host keys standing in for the database's named controls
+const CONTROLS: [(&str, Scancode); 6] = [
+    ("up", Scancode::Up),
+    ("down", Scancode::Down),
+    ("left", Scancode::Left),
+    ("right", Scancode::Right),
+    ("a", Scancode::Z),
+    ("b", Scancode::X),
+];
+
+#[derive(Debug, Clone)]
+pub struct RomEntry {
+    pub title: String,
+    // the first listed platform this emulator runs, with the rom's quirks
+    platform: Option<(Platform, Quirks)>,
+    tickrate: Option<u32>,
+    // extra host keys for keypad keys
+    pub keys: Vec<(Scancode, u8)>,
+    colours: Vec<Color>,
+}
+
+// database platform ids and the quirks they stand for
+fn platform(id: &str) -> Option<(Platform, Quirks)> {
+    match id {
+        "originalChip8" | "hybridVIP" => Some((Platform::Chip8, Quirks::VIP)),
+        "modernChip8" => Some((
+            Platform::Chip8,
+            Quirks {
+                vf_reset: false,
+                display_wait: false,
+                ..Quirks::VIP
+            },
+        )),
+        "chip48" => Some((Platform::Chip8, Quirks::CHIP48)),
+        "superchip1" => Some((
+            Platform::SuperChip,
+            Quirks {
+                index_increment: IndexIncrement::X,
+                ..Quirks::SCHIP_MODERN
+            },
+        )),
+        "superchip" => Some((Platform::SuperChip, Quirks::SCHIP_MODERN)),
+        "xochip" => Some((Platform::XoChip, Quirks::XO_CHIP)),
+        _ => None,
+    }
+}
+
+// unknown quirk names are left alone
+fn set_quirk(quirks: &mut Quirks, name: &str, on: bool) {
+    match name {
+        "shift" => quirks.shift_vx = on,
+        "memoryIncrementByX" => set_index(quirks, IndexIncrement::X, on),
+        "memoryLeaveIUnchanged" => set_index(quirks, IndexIncrement::Unchanged, on),
+        "wrap" => quirks.clip_sprites = !on,
+        "jump" => quirks.jump_vx = on,
+        "vblank" => quirks.display_wait = on,
+        "logic" => quirks.vf_reset = on,
+        _ => {}
+    }
+}
+
+// turning either index quirk off goes back to the VIP's i += x + 1
+fn set_index(quirks: &mut Quirks, index: IndexIncrement, on: bool) {
+    if on {
+        quirks.index_increment = index;
+    } else if quirks.index_increment == index {
+        quirks.index_increment = IndexIncrement::XPlusOne;
+    }
+}
+
+impl RomEntry {
+    fn new(program: &Value, rom: &Value) -> RomEntry {
+        let platform = rom["platforms"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| id.as_str())
+            .find_map(|id| {
+                let (platform, mut quirks) = platform(id)?;
+                if let Some(changes) = rom["quirkyPlatforms"][id].as_object() {
+                    for (name, on) in changes {
+                        if let Some(on) = on.as_bool() {
+                            set_quirk(&mut quirks, name, on);
+                        }
+                    }
+                }
+                Some((platform, quirks))
+            });
+
+        let keys = CONTROLS
+            .iter()
+            .filter_map(|(name, scancode)| match rom["keys"][name].as_u64() {
+                Some(key) if key < 0x10 => Some((*scancode, key as u8)),
+                _ => None,
+            })
+            .collect();
+
+        let colours = rom["colors"]["pixels"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|colour| parse_colour(colour.as_str()?).ok())
+            .take(4)
+            .collect();
+
+        RomEntry {
+            title: program["title"].as_str().unwrap_or("").to_string(),
+            platform,
+            tickrate: rom["tickrate"]
+                .as_u64()
+                .and_then(|rate| u32::try_from(rate).ok())
+                .filter(|rate| *rate > 0),
+            keys,
+            colours,
+        }
+    }
+
+    // anything given on the command line wins over the database
+    pub fn apply(&self, args: &mut Args) {
+        if let (None, Some((platform, quirks))) = (args.machine.platform, self.platform) {
+            args.machine.platform = Some(platform);
+            args.machine.quirks = args.machine.quirks.or(Some(quirks));
+        }
+        if args.machine.ipf.is_none() && args.machine.hz.is_none() {
+            args.machine.ipf = self.tickrate;
+        }
+        if args.palette.is_none() && !self.colours.is_empty() {
+            let mut palette = DEFAULT_PALETTE;
+            palette.0[..self.colours.len()].copy_from_slice(&self.colours);
+            args.palette = Some(palette);
+        }
+    }
+}
+
+// the database files given on the command line, then the user's own and the
+// community one from the config directory if they are there
+pub fn paths(given: &[String]) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = given.iter().map(PathBuf::from).collect();
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(config) = config {
+        for name in ["user.json", "programs.json"] {
+            let path = config.join("chip8emu").join(name);
+            if path.exists() {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+// the entry for rom in the first database that has it, hashes are matched
+// without regard to case
+pub fn lookup(paths: &[PathBuf], rom: &[u8]) -> Option<RomEntry> {
+    let hash = Sha1::from(rom).digest().to_string();
+    for path in paths {
+        let programs = match fs::read(path) {
+            Ok(data) => serde_json::from_slice::<Value>(&data).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let programs = match programs {
+            Ok(programs) => programs,
+            Err(e) => {
+                eprintln!("could not read rom database {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        for program in programs.as_array().into_iter().flatten() {
+            let roms = program["roms"].as_object().into_iter().flatten();
+            for (key, rom) in roms {
+                if key.eq_ignore_ascii_case(&hash) {
+                    return Some(RomEntry::new(program, rom));
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,76 @@
+// Keeps frames, and with them the timers, at 60 Hz of wall clock time
+// however long running and drawing a frame takes. Frame deadlines are
+// worked out from the last time the schedule was in sync rather than by
+// adding up 1/60 s steps, so they don't drift.
+use crate::options::Speed;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// frames a stalled host runs back to back to catch up, anything further
+// behind is dropped
+const MAX_CATCH_UP: u64 = 4;
+
+// how far into a frame of instructions an input event that happened age
+// ago goes, so events from the last frame keep their spacing a frame late
+pub fn event_offset(age: Duration, instructions: u64) -> u64 {
+    let frame = Duration::from_nanos(1_000_000_000 / 60);
+    let into = frame.saturating_sub(age).as_nanos() as u64;
+    (into * instructions / frame.as_nanos() as u64).min(instructions.saturating_sub(1))
+}
+
+pub struct Scheduler {
+    speed: Speed,
+    // frames started so far
+    frame: u64,
+    // synced_frame was due at synced_at
+    synced_frame: u64,
+    synced_at: Instant,
+}
+
+impl Scheduler {
+    pub fn new(speed: Speed) -> Scheduler {
+        Scheduler {
+            speed,
+            frame: 0,
+            synced_frame: 0,
+            synced_at: Instant::now(),
+        }
+    }
+
+    fn due_at(&self, frame: u64) -> Instant {
+        let frames = frame - self.synced_frame;
+        self.synced_at + Duration::from_nanos(frames * 1_000_000_000 / 60)
+    }
+
+    // how many frames should run now, 0 if the next one isn't due yet
+    pub fn frames_due(&mut self) -> u64 {
+        let now = Instant::now();
+        let mut due = 0;
+        while due < MAX_CATCH_UP && self.due_at(self.frame + due) <= now {
+            due += 1;
+        }
+        // still behind after catching up as far as allowed, so start the
+        // schedule again from the last of those frames
+        if due == MAX_CATCH_UP && self.due_at(self.frame + due) <= now {
+            self.synced_frame = self.frame + due - 1;
+            self.synced_at = now;
+        }
+        due
+    }
+
+    // gives the number of instructions to run in the frame
+    pub fn start_frame(&mut self) -> u64 {
+        let instructions = self.speed.instructions(self.frame);
+        self.frame += 1;
+        instructions
+    }
+
+    // sleeps until the next frame is due
+    pub fn wait(&self) {
+        let due = self.due_at(self.frame);
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+    }
+}
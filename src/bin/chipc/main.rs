@@ -1,12 +1,10 @@
-use crate::assembler::assemble;
-use clap::Parser;
+use chip8::asm::{assemble, disassemble};
+use clap::{ArgAction, Parser};
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{Read, Write},
 };
 
-mod assembler;
-
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
@@ -16,17 +14,22 @@ struct Args {
     #[arg(short = 'o', long = "out", value_name = "output file", id = "out")]
     destination_file: Option<String>,
 
-    #[arg(short = 's', long = "asm", default_value = "true")]
+    // Assemble source into a rom, or with -s false disassemble a rom
+    #[arg(short = 's', long = "asm", default_value_t = true, action = ArgAction::Set)]
     is_asm: bool,
 
     #[arg(short = 'd', long = "debug")]
     print_debug: bool,
+
+    // Write a map from instruction addresses to source lines for debuggers
+    #[arg(short = 'm', long = "map", value_name = "map file")]
+    map_file: Option<String>,
 }
 
 fn main() {
     let args = Args::parse();
 
-    let mut input_file = match File::open(args.input_file) {
+    let mut input_file = match File::open(&args.input_file) {
         Ok(f) => f,
         Err(e) => {
             println!("{}", e);
@@ -34,8 +37,8 @@ fn main() {
         }
     };
 
-    let mut source: String = String::new();
-    match input_file.read_to_string(&mut source) {
+    let mut input: Vec<u8> = Vec::new();
+    match input_file.read_to_end(&mut input) {
         Ok(_) => {}
         Err(e) => {
             println!("{}", e);
@@ -44,10 +47,10 @@ fn main() {
     }
     drop(input_file);
 
-    let output_file_name: String = if args.destination_file.is_some() {
-        args.destination_file.unwrap()
-    } else {
-        "a.out".to_string()
+    let output_file_name: String = match args.destination_file {
+        Some(name) => name,
+        None if args.is_asm => "a.out".to_string(),
+        None => "a.s".to_string(),
     };
     let mut output_file = match File::create(output_file_name) {
         Ok(f) => f,
@@ -57,30 +60,46 @@ fn main() {
         }
     };
 
-    let bin;
-    if args.is_asm {
-        bin = match assemble(&source, args.print_debug) {
-            Ok(b) => b,
-            Err(e) => {
-                println!("{:?}", e);
-                return;
-            }
-        };
-    } else {
-        bin = match assemble(&source, args.print_debug) {
-            Ok(b) => b,
-            Err(e) => {
-                println!("{:?}", e);
-                return;
-            }
-        };
+    if !args.is_asm {
+        if let Err(e) = output_file.write_all(disassemble(&input).as_bytes()) {
+            println!("{}", e);
+        }
+        return;
     }
 
-    match output_file.write_all(&bin) {
-        Ok(_) => {}
+    let source = match String::from_utf8(input) {
+        Ok(s) => s,
         Err(e) => {
             println!("{}", e);
             return;
         }
+    };
+    let (bin, lines) = match assemble(&source, args.print_debug) {
+        Ok(b) => b,
+        Err(e) => {
+            println!("line {}: {:?}", e.line, e.error);
+            return;
+        }
+    };
+
+    if let Err(e) = output_file.write_all(&bin) {
+        println!("{}", e);
+    }
+
+    if let Some(map_file) = args.map_file {
+        if let Err(e) = write_map(&map_file, &args.input_file, &lines) {
+            println!("{}", e);
+        }
+    }
+}
+
+// one "ADDR LINE" pair per instruction after a line naming the source file,
+// addresses in hex and lines counted from 1
+fn write_map(path: &str, source: &str, lines: &[(u16, usize)]) -> std::io::Result<()> {
+    let source = fs::canonicalize(source)?;
+    let mut out = format!("source {}\n", source.display());
+    for (addr, line) in lines {
+        out.push_str(&format!("{:04X} {}\n", addr, line));
     }
+    fs::write(path, out)
 }
@@ -0,0 +1,16 @@
+pub mod audio;
+mod chip8;
+#[cfg(feature = "std")]
+pub mod disasm;
+pub mod error;
+pub mod font;
+pub mod input;
+pub mod quirks;
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod rom;
+
+#[cfg(feature = "std")]
+pub use chip8::Tracer;
+pub use chip8::{Addressing, Chip8, Chip8Mode, DefaultStorage, Platform, Storage};
+pub use error::{Chip8Error, Chip8ErrorKind, FaultAction, FaultPolicy};
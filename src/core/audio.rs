@@ -0,0 +1,121 @@
+// snapshot of everything needed to generate sound for one frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AudioState {
+    // 1-bit samples, played most significant bit first
+    pub pattern: [u8; 16],
+    pub pitch: u8,
+    pub playing: bool,
+}
+
+// plain beep used until a rom loads its own pattern with F002
+pub const DEFAULT_PATTERN: [u8; 16] = [0xF0; 16];
+pub const DEFAULT_PITCH: u8 = 64;
+
+// float powers come from std, so without it hosts work out the rate and
+// produce samples themselves
+#[cfg(feature = "std")]
+impl AudioState {
+    // pattern playback rate in bits per second
+    pub fn bit_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+}
+
+// turns audio state into samples for whatever audio backend the frontend uses
+#[cfg(feature = "std")]
+pub struct SampleProducer {
+    sample_rate: u32,
+    volume: f32,
+    // position within the 128 bit pattern
+    phase: f64,
+}
+
+#[cfg(feature = "std")]
+impl SampleProducer {
+    pub fn new(sample_rate: u32, volume: f32) -> SampleProducer {
+        SampleProducer {
+            sample_rate,
+            volume,
+            phase: 0.0,
+        }
+    }
+
+    pub fn fill(&mut self, state: &AudioState, out: &mut [f32]) {
+        if !state.playing {
+            self.phase = 0.0;
+            out.fill(0.0);
+            return;
+        }
+
+        let step = state.bit_rate() / self.sample_rate as f64;
+        for sample in out.iter_mut() {
+            let bit = self.phase as usize % 128;
+            let on = state.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if on { self.volume } else { -self.volume };
+            self.phase = (self.phase + step) % 128.0;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{AudioState, SampleProducer, DEFAULT_PATTERN, DEFAULT_PITCH};
+    use crate::core::{Chip8, Chip8Mode};
+
+    fn state(pattern: [u8; 16], pitch: u8) -> AudioState {
+        AudioState {
+            pattern,
+            pitch,
+            playing: true,
+        }
+    }
+
+    #[test]
+    fn pitch_sets_the_bit_rate() {
+        // 4000 * 2 ^ ((pitch - 64) / 48)
+        assert_eq!(state(DEFAULT_PATTERN, 64).bit_rate(), 4000.0);
+        assert_eq!(state(DEFAULT_PATTERN, 112).bit_rate(), 8000.0);
+        assert_eq!(state(DEFAULT_PATTERN, 16).bit_rate(), 2000.0);
+        let rate = state(DEFAULT_PATTERN, 88).bit_rate();
+        assert!((rate - 4000.0 * 2f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pattern_plays_high_bit_first() {
+        let mut pattern = [0; 16];
+        pattern[0] = 0b1010_0000;
+        pattern[15] = 0x01;
+        // one sample per bit at the default pitch
+        let mut producer = SampleProducer::new(4000, 0.5);
+        let mut out = [0.0; 129];
+        producer.fill(&state(pattern, DEFAULT_PITCH), &mut out);
+        assert_eq!(out[..4], [0.5, -0.5, 0.5, -0.5]);
+        assert_eq!(out[126], -0.5);
+        assert_eq!(out[127], 0.5);
+        // then it starts over
+        assert_eq!(out[128], 0.5);
+    }
+
+    #[test]
+    fn silent_while_the_sound_timer_is_zero() {
+        let mut emu = Chip8::new();
+        // V0 = 2, ST = V0, loop
+        emu.load_rom_bytes(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04], 0x200)
+            .unwrap();
+        emu.mode = Chip8Mode::Running;
+        let mut producer = SampleProducer::new(4000, 0.5);
+        let mut out = [1.0; 16];
+        producer.fill(&emu.get_audio_state(), &mut out);
+        assert_eq!(out, [0.0; 16]);
+
+        emu.clock().unwrap();
+        emu.clock().unwrap();
+        producer.fill(&emu.get_audio_state(), &mut out);
+        assert!(out.iter().all(|sample| *sample != 0.0));
+
+        emu.signal_new_frame();
+        emu.signal_new_frame();
+        producer.fill(&emu.get_audio_state(), &mut out);
+        assert_eq!(out, [0.0; 16]);
+    }
+}
@@ -0,0 +1,948 @@
+use crate::core::audio::{AudioState, DEFAULT_PATTERN, DEFAULT_PITCH};
+use crate::core::error::{Chip8Error, Chip8ErrorKind, FaultAction, FaultPolicy};
+use crate::core::font::Font;
+use crate::core::input::{InputQueue, KeyEvent};
+use crate::core::quirks::{IndexIncrement, Quirks};
+use crate::core::rng::Rng;
+#[cfg(feature = "std")]
+use crate::core::rom;
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+
+mod memory;
+#[cfg(feature = "std")]
+mod savestate;
+#[cfg(test)]
+mod tests;
+#[cfg(feature = "std")]
+mod trace;
+
+pub use memory::{Addressing, Storage};
+#[cfg(feature = "std")]
+pub use trace::Tracer;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match name {
+            "chip8" => Some(Platform::Chip8),
+            "schip" => Some(Platform::SuperChip),
+            "xo-chip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn default_quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::VIP,
+            Platform::SuperChip => Quirks::SCHIP_MODERN,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    pub fn default_font(&self) -> Font {
+        match self {
+            Platform::Chip8 => Font::VIP,
+            Platform::SuperChip => Font::SCHIP,
+            Platform::XoChip => Font::OCTO,
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => 0x1000,
+            Platform::XoChip => 0x10000,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Chip8Mode {
+    Running,
+    WaitingKey,
+    Stopped,
+}
+
+// where a Chip8 keeps its memory unless told otherwise, a Vec with std and a
+// buffer the host sets aside without it
+#[cfg(feature = "std")]
+pub type DefaultStorage = Vec<u8>;
+#[cfg(not(feature = "std"))]
+pub type DefaultStorage = &'static mut [u8];
+
+pub struct Chip8<M: Storage = DefaultStorage> {
+    v: [u8; 0x10],
+    pc: u16,
+    i: u16,
+    stack: [u16; 24],
+    stack_pos: u8,
+    delay_timer: u8,
+    sound_timer: u8,
+    // only the first memory_size() bytes are in use
+    memory: M,
+
+    // bit 0 is drawing plane 1, bit 1 is plane 2
+    pixels: [[u8; 128]; 64],
+    planes: u8,
+    hires: bool,
+    rpl: [u8; 0x10],
+    pattern: [u8; 16],
+    pitch: u8,
+    down_keys: [bool; 0x10],
+    // while FX0A waits, the register it loads and the key pressed so far
+    wait_reg: u8,
+    wait_key: Option<u8>,
+    input: InputQueue,
+    sprite_drawn: bool,
+    pub mode: Chip8Mode,
+    pub quirks: Quirks,
+    pub faults: FaultPolicy,
+    pub addressing: Addressing,
+    platform: Platform,
+    // where FX29 finds the small glyphs, the big ones follow them
+    font_addr: u16,
+    big_glyphs: u8,
+    rng: Rng,
+    // every CXNN value is appended here while a movie is being recorded
+    #[cfg(feature = "std")]
+    pub random_log: Option<Vec<u8>>,
+    // CXNN takes its values from here instead of the rng during playback
+    #[cfg(feature = "std")]
+    pub random_replay: Option<VecDeque<u8>>,
+    // every key event is appended here with the cycle it took effect while
+    // a movie is being recorded
+    #[cfg(feature = "std")]
+    pub key_log: Option<Vec<KeyEvent>>,
+    // instructions run since power on, counting each clock spent waiting
+    // on FX0A as one
+    cycles: u64,
+    #[cfg(feature = "std")]
+    tracer: Option<Tracer>,
+}
+
+// enough for xo-chip, the largest platform
+#[cfg(feature = "std")]
+pub(crate) const MAX_MEMORY: usize = 0x10000;
+
+#[cfg(feature = "std")]
+impl Default for Chip8 {
+    fn default() -> Chip8 {
+        Chip8::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Chip8 {
+    pub fn new() -> Chip8 {
+        Chip8::with_memory(vec![0; MAX_MEMORY])
+    }
+}
+
+impl<M: Storage> Chip8<M> {
+    // memory has to hold at least the 0x1000 bytes of chip8 and schip, on
+    // xo-chip anything past its end is left out of the address space
+    pub fn try_with_memory(mut memory: M) -> Result<Chip8<M>, Chip8Error> {
+        if memory.as_ref().len() < 0x1000 {
+            return Err(Chip8ErrorKind::MemoryTooSmall.into());
+        }
+        memory.as_mut().fill(0);
+        let mut chip8 = Chip8::blank(memory);
+        chip8.write_font(&Platform::Chip8.default_font());
+        Ok(chip8)
+    }
+
+    // like try_with_memory, but panics when memory is too small
+    pub fn with_memory(memory: M) -> Chip8<M> {
+        match Chip8::try_with_memory(memory) {
+            Ok(chip8) => chip8,
+            Err(e) => panic!("chip8 {}", e),
+        }
+    }
+
+    // the power on state with memory as it is given
+    fn blank(memory: M) -> Chip8<M> {
+        Chip8 {
+            v: [0; 0x10],
+            pc: 0x200,
+            i: 0x000,
+            stack: [0x00; 24],
+            stack_pos: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            memory,
+            pixels: [[0; 128]; 64],
+            planes: 0x1,
+            hires: false,
+            rpl: [0; 0x10],
+            pattern: DEFAULT_PATTERN,
+            pitch: DEFAULT_PITCH,
+            down_keys: [false; 0x10],
+            wait_reg: 0,
+            wait_key: None,
+            input: InputQueue::new(),
+            sprite_drawn: false,
+            mode: Chip8Mode::Stopped,
+            quirks: Quirks::default(),
+            faults: FaultPolicy::default(),
+            addressing: Addressing::default(),
+            platform: Platform::Chip8,
+            font_addr: 0,
+            big_glyphs: 0,
+            rng: Rng::default(),
+            #[cfg(feature = "std")]
+            random_log: None,
+            #[cfg(feature = "std")]
+            random_replay: None,
+            #[cfg(feature = "std")]
+            key_log: None,
+            cycles: 0,
+            #[cfg(feature = "std")]
+            tracer: None,
+        }
+    }
+
+    pub fn clock(&mut self) -> Result<(), Chip8Error> {
+        while let Some(event) = self.input.pop_due(self.cycles) {
+            self.apply_key(event);
+        }
+        // FX0A blocks until a key event lets it finish, but time still passes
+        if self.mode == Chip8Mode::WaitingKey {
+            self.cycles += 1;
+            return Ok(());
+        }
+        #[cfg(feature = "std")]
+        if self.tracer.is_some() {
+            if let Ok(instr) = self.read_word(self.pc as usize) {
+                self.trace_instr(self.pc, instr);
+            }
+        }
+        let pc = self.pc;
+        self.cycles += 1;
+        let (instr, operand) = match self.fetch_instr() {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                self.fault(e, pc)?;
+                // ignored, so step over the word that couldn't be read or
+                // the next clock would fault on it again
+                self.pc = self.pc.wrapping_add(2);
+                return Ok(());
+            }
+        };
+        match self.execute_instr(instr, operand) {
+            Ok(()) => Ok(()),
+            Err(e) => self.fault(e, pc),
+        }
+    }
+
+    // fills in where the instruction at pc failed and applies the fault
+    // policy to it
+    fn fault(&mut self, error: Chip8Error, pc: u16) -> Result<(), Chip8Error> {
+        let error = Chip8Error {
+            pc: Some(pc),
+            opcode: self.read_word(pc as usize).ok(),
+            stack_depth: Some(self.stack_pos),
+            ..error
+        };
+        match self.faults.action(error.kind) {
+            FaultAction::Halt => {
+                self.mode = Chip8Mode::Stopped;
+                Err(error)
+            }
+            FaultAction::Ignore => Ok(()),
+            FaultAction::Pause => {
+                self.pc = pc;
+                Err(error)
+            }
+        }
+    }
+
+    // runs up to instructions instructions, fewer if the program stops,
+    // then moves on to the next 60 Hz frame
+    pub fn run_frame(&mut self, instructions: usize) -> Result<(), Chip8Error> {
+        for _ in 0..instructions {
+            if self.mode == Chip8Mode::Stopped {
+                break;
+            }
+            self.clock()?;
+        }
+        self.signal_new_frame();
+        Ok(())
+    }
+
+    // queues a key event for the instruction starting at event.cycle, one
+    // for a cycle that has already run applies before the next instruction
+    pub fn queue_key(&mut self, event: KeyEvent) {
+        if let Some(early) = self.input.push(event) {
+            self.apply_key(early);
+        }
+    }
+
+    fn apply_key(&mut self, event: KeyEvent) {
+        if event.down {
+            self.key_down(event.key);
+        } else {
+            self.key_up(event.key);
+        }
+    }
+
+    // key events take effect straight away, so a key pressed and released
+    // between two instructions still finishes FX0A
+    pub fn key_down(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.log_key(key, true);
+        self.down_keys[key as usize] = true;
+        if self.mode == Chip8Mode::WaitingKey && self.wait_key.is_none() {
+            if self.quirks.key_wait_press {
+                self.finish_key_wait(key);
+            } else {
+                self.wait_key = Some(key);
+            }
+        }
+    }
+
+    // like the cosmac vip, FX0A finishes when the key it saw go down is
+    // released
+    pub fn key_up(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.log_key(key, false);
+        self.down_keys[key as usize] = false;
+        if self.mode == Chip8Mode::WaitingKey && self.wait_key == Some(key) {
+            self.finish_key_wait(key);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn log_key(&mut self, key: u8, down: bool) {
+        let cycle = self.cycles;
+        if let Some(log) = self.key_log.as_mut() {
+            log.push(KeyEvent { cycle, key, down });
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn log_key(&mut self, _key: u8, _down: bool) {}
+
+    pub fn is_key_down(&self, key: u8) -> bool {
+        self.down_keys[key as usize & 0xF]
+    }
+
+    fn finish_key_wait(&mut self, key: u8) {
+        self.v[self.wait_reg as usize] = key;
+        self.wait_key = None;
+        self.mode = Chip8Mode::Running;
+    }
+
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
+
+    // a clone of this can be handed back to set_rng to replay the sequence
+    pub fn get_rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    // resizes memory to fit the platform and loads its font, so call this
+    // before loading a rom or another font. The font stays where it was
+    // unless the platform's own doesn't fit there, then it goes back to 0
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        let size = self.memory_size();
+        self.memory.as_mut()[size..].fill(0);
+        let font = platform.default_font();
+        if self.font_addr as usize + font.size() > size {
+            self.font_addr = 0;
+        }
+        self.write_font(&font);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load_rom(&mut self, filename: &str, address: u16) -> Result<(), Chip8Error> {
+        let rom = rom::read_rom(filename)?;
+        self.load_rom_bytes(&rom, address)
+    }
+
+    // the rom has to fit whole between address and the end of memory, and
+    // runs from address
+    pub fn load_rom_bytes(&mut self, rom: &[u8], address: u16) -> Result<(), Chip8Error> {
+        let start = address as usize;
+        let size = self.memory_size();
+        if start >= size {
+            return Err(Chip8Error::address_overflow(start));
+        }
+        if rom.is_empty() {
+            return Err(Chip8ErrorKind::EmptyRom.into());
+        }
+        if rom.len() > size - start {
+            return Err(Chip8ErrorKind::RomTooLarge.into());
+        }
+        self.memory.as_mut()[start..start + rom.len()].copy_from_slice(rom);
+        self.pc = address;
+        Ok(())
+    }
+
+    // FX29 and FX30 point into the font wherever it is loaded, FX30 picks a
+    // big glyph modulo their count so there has to be at least one
+    pub fn set_font(&mut self, font: &Font, addr: u16) -> Result<(), Chip8Error> {
+        if font.big_glyphs == 0 || font.big_glyphs > 16 {
+            return Err(Chip8ErrorKind::InvalidFont.into());
+        }
+        let size = self.memory_size();
+        if addr as usize + font.size() > size {
+            return Err(Chip8Error::address_overflow((addr as usize).max(size)));
+        }
+        self.font_addr = addr;
+        self.write_font(font);
+        Ok(())
+    }
+
+    fn write_font(&mut self, font: &Font) {
+        let start = self.font_addr as usize;
+        let big = font.big_glyphs as usize * 10;
+        self.memory.as_mut()[start..start + 80].copy_from_slice(&font.small);
+        self.memory.as_mut()[start + 80..start + 80 + big].copy_from_slice(&font.big[..big]);
+        self.big_glyphs = font.big_glyphs;
+    }
+
+    pub fn get_font_address(&self) -> u16 {
+        self.font_addr
+    }
+
+    // only the top left get_resolution() corner of the buffer is in use,
+    // each pixel holds one bit per drawing plane
+    pub fn get_pixels(&self) -> &[[u8; 128]; 64] {
+        &self.pixels
+    }
+
+    pub fn get_resolution(&self) -> (usize, usize) {
+        if self.hires {
+            (128, 64)
+        } else {
+            (64, 32)
+        }
+    }
+
+    pub fn get_registers(&self) -> &[u8; 0x10] {
+        &self.v
+    }
+
+    pub fn get_index(&self) -> u16 {
+        self.i
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        self.pc
+    }
+
+    // return addresses, innermost call last
+    pub fn get_stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pos as usize]
+    }
+
+    pub fn get_memory(&self) -> &[u8] {
+        &self.memory.as_ref()[..self.memory_size()]
+    }
+
+    // always refuses to go past the end, whatever the addressing mode
+    pub fn write_memory(&mut self, addr: u16, data: &[u8]) -> Result<(), Chip8Error> {
+        let size = self.memory_size();
+        if addr as usize + data.len() > size {
+            return Err(Chip8Error::address_overflow((addr as usize).max(size)));
+        }
+        self.memory.as_mut()[addr as usize..addr as usize + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    pub fn set_register(&mut self, reg: usize, value: u8) {
+        self.v[reg & 0xF] = value;
+    }
+
+    pub fn set_index(&mut self, value: u16) {
+        self.i = value;
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    pub fn get_platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn get_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn get_sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn get_audio_state(&self) -> AudioState {
+        AudioState {
+            pattern: self.pattern,
+            pitch: self.pitch,
+            playing: self.sound_timer > 0,
+        }
+    }
+
+    pub fn signal_new_frame(&mut self) {
+        self.sprite_drawn = false;
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    // returns the instruction and the second word of xo-chip's 4 byte F000 NNNN
+    fn fetch_instr(&mut self) -> Result<(u16, u16), Chip8Error> {
+        let instr = self.read_word(self.pc as usize)?;
+        self.pc = self.pc.wrapping_add(2);
+
+        if instr == 0xF000 && self.platform == Platform::XoChip {
+            let operand = self.read_word(self.pc as usize)?;
+            self.pc = self.pc.wrapping_add(2);
+            return Ok((instr, operand));
+        }
+
+        Ok((instr, 0))
+    }
+
+    // skips the next instruction, which is 4 bytes long if it is F000 NNNN
+    fn skip_instr(&mut self) {
+        if self.platform == Platform::XoChip && self.read_word(self.pc as usize) == Ok(0xF000) {
+            self.pc = self.pc.wrapping_add(4);
+        } else {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn execute_instr(&mut self, instr: u16, operand: u16) -> Result<(), Chip8Error> {
+        let opcode: u8 = ((instr & 0xF000) >> (4 * 3)) as u8;
+        let x: usize = ((instr & 0x0F00) >> (4 * 2)) as usize;
+        let y: usize = ((instr & 0x00F0) >> 4) as usize;
+        let addr: u16 = instr & 0x0FFF;
+        let imm_8: u8 = (instr & 0x00FF) as u8;
+        let imm_4: u8 = (instr & 0x000F) as u8;
+
+        let schip = self.platform != Platform::Chip8;
+        let xo = self.platform == Platform::XoChip;
+
+        match opcode {
+            0x0 if schip && x == 0 && y == 0xC => self.scroll_down(imm_4 as usize),
+            0x0 if xo && x == 0 && y == 0xD => self.scroll_up(imm_4 as usize),
+            0x0 => match imm_8 {
+                0xE0 => self.clear_screen(),
+                // return
+                0xEE => {
+                    if self.stack_pos == 0 {
+                        return Err(Chip8ErrorKind::StackUnderflow.into());
+                    }
+
+                    self.stack_pos -= 1;
+                    self.pc = self.stack[self.stack_pos as usize];
+                }
+                0x01 => self.mode = Chip8Mode::Stopped,
+                0xFB if schip => self.scroll_right(4),
+                0xFC if schip => self.scroll_left(4),
+                0xFD if schip => self.mode = Chip8Mode::Stopped,
+                0xFE if schip => {
+                    self.hires = false;
+                    self.clear_screen();
+                }
+                0xFF if schip => {
+                    self.hires = true;
+                    self.clear_screen();
+                }
+                _ => return Err(Chip8ErrorKind::InvalidInstruction.into()),
+            },
+            // jump addr
+            0x1 => {
+                // can never be out of bounds
+                self.pc = addr;
+            }
+            // call addr
+            0x2 => {
+                if self.stack_pos as usize >= self.stack.len() {
+                    return Err(Chip8ErrorKind::StackOverflow.into());
+                }
+
+                self.stack[self.stack_pos as usize] = self.pc;
+                self.stack_pos += 1;
+                self.pc = addr;
+            }
+            // if equal
+            0x3 => {
+                if self.v[x] == imm_8 {
+                    self.skip_instr();
+                }
+            }
+            // if not equal
+            0x4 => {
+                if self.v[x] != imm_8 {
+                    self.skip_instr();
+                }
+            }
+            // save vx..vy, counting down if y < x
+            0x5 if xo && imm_4 == 0x2 => {
+                let count = x.abs_diff(y) + 1;
+                self.check_range(self.i as usize, count)?;
+                for offset in 0..count {
+                    let reg = if x <= y { x + offset } else { x - offset };
+                    self.write_byte(self.i as usize + offset, self.v[reg])?;
+                }
+            }
+            // load vx..vy, counting down if y < x
+            0x5 if xo && imm_4 == 0x3 => {
+                let count = x.abs_diff(y) + 1;
+                self.check_range(self.i as usize, count)?;
+                for offset in 0..count {
+                    let reg = if x <= y { x + offset } else { x - offset };
+                    self.v[reg] = self.read_byte(self.i as usize + offset)?;
+                }
+            }
+            // if equal
+            0x5 => {
+                if self.v[x] == self.v[y] {
+                    self.skip_instr();
+                }
+            }
+            // set reg
+            0x6 => {
+                self.v[x] = imm_8;
+            }
+            // add imm
+            0x7 => {
+                self.v[x] = self.v[x].wrapping_add(imm_8);
+            }
+            0x8 => match imm_4 {
+                0x0 => {
+                    self.v[x] = self.v[y];
+                }
+                0x1 => {
+                    self.v[x] |= self.v[y];
+                    if self.quirks.vf_reset {
+                        self.v[0xF] = 0x00;
+                    }
+                }
+                0x2 => {
+                    self.v[x] &= self.v[y];
+                    if self.quirks.vf_reset {
+                        self.v[0xF] = 0x00;
+                    }
+                }
+                0x3 => {
+                    self.v[x] ^= self.v[y];
+                    if self.quirks.vf_reset {
+                        self.v[0xF] = 0x00;
+                    }
+                }
+                0x4 => {
+                    let flag = if self.v[x].checked_add(self.v[y]).is_none() {
+                        0x01
+                    } else {
+                        0x00
+                    };
+
+                    self.v[x] = self.v[x].wrapping_add(self.v[y]);
+                    self.v[0xF] = flag;
+                }
+                0x5 => {
+                    let flag = if self.v[y] > self.v[x] { 0x00 } else { 0x01 };
+
+                    self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+                    self.v[0xF] = flag;
+                }
+                0x6 => {
+                    // chip8 quirk: x = y >> 1 unless shifting in place
+                    let src = if self.quirks.shift_vx { x } else { y };
+                    let flag = self.v[src] & 0x01;
+                    self.v[x] = self.v[src] >> 1;
+                    self.v[0xF] = flag;
+                }
+                0x7 => {
+                    let flag = if self.v[x] > self.v[y] { 0x00 } else { 0x01 };
+
+                    self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                    self.v[0xF] = flag;
+                }
+                0xE => {
+                    // chip8 quirk: x = y << 1 unless shifting in place
+                    let src = if self.quirks.shift_vx { x } else { y };
+                    let flag = (self.v[src] & 0x80) >> 7;
+                    self.v[x] = self.v[src] << 1;
+                    self.v[0xF] = flag;
+                }
+                _ => return Err(Chip8ErrorKind::InvalidInstruction.into()),
+            },
+            // if not equal
+            0x9 => {
+                if self.v[x] != self.v[y] {
+                    self.skip_instr();
+                }
+            }
+            0xA => self.i = addr,
+            // jump reg
+            0xB => {
+                // chip8 quirk: chip-48 and schip read the offset from vx
+                let reg = if self.quirks.jump_vx { x } else { 0 };
+                let target = addr as usize + self.v[reg] as usize;
+                self.pc = self.resolve(target)? as u16;
+            }
+            // rand
+            0xC => self.v[x] = self.next_random() & imm_8,
+            0xD => self.display_sprite(self.v[x] as usize, self.v[y] as usize, imm_4)?,
+            0xE => match imm_8 {
+                0x9E => {
+                    if self.is_key_down(self.v[x]) {
+                        self.skip_instr();
+                    }
+                }
+                0xA1 => {
+                    if !self.is_key_down(self.v[x]) {
+                        self.skip_instr();
+                    }
+                }
+                _ => return Err(Chip8ErrorKind::InvalidInstruction.into()),
+            },
+            0xF => match imm_8 {
+                // long index load
+                0x00 if xo && x == 0 => self.i = operand,
+                // select drawing planes
+                0x01 if xo => self.planes = x as u8 & 0x3,
+                // load audio pattern
+                0x02 if xo && x == 0 => {
+                    self.check_range(self.i as usize, 16)?;
+                    for offset in 0..16 {
+                        self.pattern[offset] = self.read_byte(self.i as usize + offset)?;
+                    }
+                }
+                0x07 => self.v[x] = self.delay_timer,
+                // only keys pressed from now on count, the timers keep
+                // running while it waits
+                0x0A => {
+                    self.mode = Chip8Mode::WaitingKey;
+                    self.wait_reg = x as u8;
+                    self.wait_key = None;
+                }
+                0x15 => self.delay_timer = self.v[x],
+                0x18 => self.sound_timer = self.v[x],
+                0x3A if xo => self.pitch = self.v[x],
+                0x1E => self.i = self.i.wrapping_add(self.v[x] as u16),
+                0x29 => self.i = self.get_sprite_addr(self.v[x]),
+                0x30 if schip => self.i = self.get_big_sprite_addr(self.v[x]),
+                0x33 => {
+                    self.check_range(self.i as usize, 3)?;
+
+                    self.write_byte(self.i as usize, self.v[x] / 100)?;
+                    self.write_byte(self.i as usize + 1, self.v[x] % 100 / 10)?;
+                    self.write_byte(self.i as usize + 2, self.v[x] % 10)?;
+                }
+                0x55 => {
+                    self.check_range(self.i as usize, x + 1)?;
+
+                    for offset in 0..=x {
+                        self.write_byte(self.i as usize + offset, self.v[offset])?;
+                    }
+                    self.increment_index(x);
+                }
+                0x65 => {
+                    self.check_range(self.i as usize, x + 1)?;
+
+                    for offset in 0..=x {
+                        self.v[offset] = self.read_byte(self.i as usize + offset)?;
+                    }
+                    self.increment_index(x);
+                }
+                0x75 if schip => self.rpl[..=x].copy_from_slice(&self.v[..=x]),
+                0x85 if schip => self.v[..=x].copy_from_slice(&self.rpl[..=x]),
+
+                _ => return Err(Chip8ErrorKind::InvalidInstruction.into()),
+            },
+            _ => return Err(Chip8ErrorKind::InvalidInstruction.into()),
+        }
+
+        Ok(())
+    }
+
+    // i is a plain register, only the accesses made through it wrap or trap
+    fn increment_index(&mut self, x: usize) {
+        // chip8 quirk
+        match self.quirks.index_increment {
+            IndexIncrement::XPlusOne => self.i = self.i.wrapping_add(x as u16 + 1),
+            IndexIncrement::X => self.i = self.i.wrapping_add(x as u16),
+            IndexIncrement::Unchanged => {}
+        }
+    }
+
+    // only the selected planes are cleared and scrolled
+    fn clear_screen(&mut self) {
+        for row in &mut self.pixels {
+            for pix in row {
+                *pix &= !self.planes;
+            }
+        }
+    }
+
+    // moves the selected planes of the pixel at (from_x, from_y) to (x, y)
+    fn move_pixel(&mut self, from: Option<(usize, usize)>, x: usize, y: usize) {
+        let moved = match from {
+            Some((from_x, from_y)) => self.pixels[from_y][from_x] & self.planes,
+            None => 0,
+        };
+        self.pixels[y][x] = (self.pixels[y][x] & !self.planes) | moved;
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let (width, height) = self.get_resolution();
+        for row in (0..height).rev() {
+            for col in 0..width {
+                let from = if row >= n { Some((col, row - n)) } else { None };
+                self.move_pixel(from, col, row);
+            }
+        }
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let (width, height) = self.get_resolution();
+        for row in 0..height {
+            for col in 0..width {
+                let from = if row + n < height {
+                    Some((col, row + n))
+                } else {
+                    None
+                };
+                self.move_pixel(from, col, row);
+            }
+        }
+    }
+
+    fn scroll_right(&mut self, n: usize) {
+        let (width, height) = self.get_resolution();
+        for row in 0..height {
+            for col in (0..width).rev() {
+                let from = if col >= n { Some((col - n, row)) } else { None };
+                self.move_pixel(from, col, row);
+            }
+        }
+    }
+
+    fn scroll_left(&mut self, n: usize) {
+        let (width, height) = self.get_resolution();
+        for row in 0..height {
+            for col in 0..width {
+                let from = if col + n < width {
+                    Some((col + n, row))
+                } else {
+                    None
+                };
+                self.move_pixel(from, col, row);
+            }
+        }
+    }
+
+    fn display_sprite(&mut self, x: usize, y: usize, size: u8) -> Result<(), Chip8Error> {
+        // chip8 quirk: wait for vblank, schip only waits in lores
+        if self.quirks.display_wait && !self.hires {
+            if self.sprite_drawn {
+                self.pc = self.pc.wrapping_sub(2);
+                return Ok(());
+            }
+            self.sprite_drawn = true;
+        }
+
+        let mut collision: u8 = 0;
+        let (width, height) = self.get_resolution();
+        let x = x % width;
+        let y = y % height;
+
+        // schip draws a 16x16 sprite for DXY0
+        let (rows, cols) = if size == 0 && self.platform != Platform::Chip8 {
+            (16, 16)
+        } else {
+            (size as usize, 8)
+        };
+
+        // xo-chip stores the sprite for each selected plane one after another
+        let planes = (self.planes & 0x1) + (self.planes >> 1 & 0x1);
+        self.check_range(self.i as usize, planes as usize * rows * cols / 8)?;
+        let mut sprite_addr = self.i as usize;
+        for plane in [0x1, 0x2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            for row in 0..rows {
+                // chip8 quirk: clip at the edges instead of wrapping
+                if y + row >= height && self.quirks.clip_sprites {
+                    break;
+                }
+
+                let sprite: u16 = if cols == 16 {
+                    self.read_word(sprite_addr + row * 2)?
+                } else {
+                    (self.read_byte(sprite_addr + row)? as u16) << 8
+                };
+                for bit_index in 0..cols {
+                    if x + bit_index >= width && self.quirks.clip_sprites {
+                        break;
+                    }
+
+                    let bit = (sprite << bit_index) & 0x8000 == 0x8000;
+                    let pixel = &mut self.pixels[(y + row) % height][(x + bit_index) % width];
+                    if bit {
+                        if *pixel & plane != 0 {
+                            collision = 1;
+                        }
+                        *pixel ^= plane;
+                    }
+                }
+            }
+            sprite_addr += rows * cols / 8;
+        }
+        self.v[0xF] = collision;
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn next_random(&mut self) -> u8 {
+        let value = match self.random_replay.as_mut().and_then(|r| r.pop_front()) {
+            Some(value) => value,
+            None => self.rng.next_u8(self.memory.as_ref()),
+        };
+        if let Some(log) = self.random_log.as_mut() {
+            log.push(value);
+        }
+        value
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn next_random(&mut self) -> u8 {
+        self.rng.next_u8(self.memory.as_ref())
+    }
+
+    fn get_sprite_addr(&self, index: u8) -> u16 {
+        // each character takes up 5 bytes, only the low digit counts
+        self.font_addr + (index as u16 & 0xF) * 5
+    }
+
+    fn get_big_sprite_addr(&self, index: u8) -> u16 {
+        // each big character takes up 10 bytes after the 16 small ones, some
+        // fonts only have the digits
+        self.font_addr + 80 + (index as u16 % self.big_glyphs as u16) * 10
+    }
+}
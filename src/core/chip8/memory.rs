@@ -0,0 +1,59 @@
+// Every memory access made by an instruction goes through here, so what
+// happens at the end of memory is decided in one place. The COSMAC VIP
+// ignores the address bits above its memory size, which is what Wrap does,
+// Trap raises AddressOverflow instead.
+use super::Chip8;
+use crate::core::error::Chip8Error;
+
+// anything holding the bytes of memory, a Vec on a desktop or a slice of a
+// static buffer on a microcontroller
+pub trait Storage: AsRef<[u8]> + AsMut<[u8]> {}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Storage for T {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Addressing {
+    Wrap,
+    #[default]
+    Trap,
+}
+
+impl<M: Storage> Chip8<M> {
+    // the platform's memory, or as much of it as the storage holds
+    pub(super) fn memory_size(&self) -> usize {
+        self.platform.memory_size().min(self.memory.as_ref().len())
+    }
+
+    pub(super) fn resolve(&self, addr: usize) -> Result<usize, Chip8Error> {
+        let size = self.memory_size();
+        match self.addressing {
+            _ if addr < size => Ok(addr),
+            Addressing::Wrap => Ok(addr % size),
+            Addressing::Trap => Err(Chip8Error::address_overflow(addr)),
+        }
+    }
+
+    pub(super) fn read_byte(&self, addr: usize) -> Result<u8, Chip8Error> {
+        Ok(self.memory.as_ref()[self.resolve(addr)?])
+    }
+
+    pub(super) fn write_byte(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let addr = self.resolve(addr)?;
+        self.memory.as_mut()[addr] = value;
+        Ok(())
+    }
+
+    pub(super) fn read_word(&self, addr: usize) -> Result<u16, Chip8Error> {
+        Ok((self.read_byte(addr)? as u16) << 8 | self.read_byte(addr + 1)? as u16)
+    }
+
+    // checked before instructions that touch several bytes, so under Trap
+    // they fail before changing anything
+    pub(super) fn check_range(&self, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        if len > 0 {
+            self.resolve(addr + len - 1)?;
+        }
+        self.resolve(addr)?;
+        Ok(())
+    }
+}
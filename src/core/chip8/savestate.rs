@@ -0,0 +1,254 @@
+// Save state format, all multi-byte values are little endian:
+//
+//   magic          4 bytes  "C8SS"
+//   version        u16      STATE_VERSION
+//   platform       u8       0 = chip8, 1 = schip, 2 = xo-chip
+//   v              16 bytes
+//   pc             u16
+//   i              u16
+//   stack          24 x u16
+//   stack_pos      u8
+//   delay_timer    u8
+//   sound_timer    u8
+//   memory         u32 length followed by that many bytes
+//   pixels         128 x 64 bytes, one per pixel, row major
+//   planes         u8
+//   hires          u8
+//   rpl            16 bytes
+//   pattern        16 bytes
+//   pitch          u8
+//   down_keys      u16, bit n set if key n is down
+//   wait_key       u8, key FX0A saw go down, 0xFF if none
+//   sprite_drawn   u8
+//   mode           u8       0 = running, 1 = waiting for key, 2 = stopped
+//   rng            u8 kind followed by u64 state, since version 2
+//                  0 = thread or external (state unused), 1 = seeded,
+//                  2 = vip with the pointer in the low byte and the seed in
+//                  the next byte
+//   cycles         u64, since version 3
+//   wait_reg       u8, register FX0A loads, since version 4
+//
+// Version 1 states are loaded by keeping the emulator's current rng, states
+// before version 3 start counting cycles from 0. Nothing waited in the
+// WaitingKey mode before version 4.
+use super::{Chip8, Chip8Mode, Platform, Storage};
+use crate::core::error::{Chip8Error, Chip8ErrorKind};
+use crate::core::rng::Rng;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+pub const STATE_VERSION: u16 = 4;
+
+struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.pos + len > self.data.len() {
+            return Err(Chip8ErrorKind::InvalidSaveState.into());
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Chip8Error> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, Chip8Error> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn bool(&mut self) -> Result<bool, Chip8Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Chip8ErrorKind::InvalidSaveState.into()),
+        }
+    }
+}
+
+impl<M: Storage> Chip8<M> {
+    pub fn save_state(&self) -> Vec<u8> {
+        let memory = self.get_memory();
+        let mut out = Vec::with_capacity(memory.len() + 128 * 64 + 256);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        out.push(match self.platform {
+            Platform::Chip8 => 0,
+            Platform::SuperChip => 1,
+            Platform::XoChip => 2,
+        });
+        out.extend_from_slice(&self.v);
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&self.i.to_le_bytes());
+        for addr in self.stack {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+        out.push(self.stack_pos);
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.extend_from_slice(&(memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(memory);
+        for row in &self.pixels {
+            out.extend_from_slice(row);
+        }
+        out.push(self.planes);
+        out.push(self.hires as u8);
+        out.extend_from_slice(&self.rpl);
+        out.extend_from_slice(&self.pattern);
+        out.push(self.pitch);
+        let mut keys: u16 = 0;
+        for (index, down) in self.down_keys.iter().enumerate() {
+            keys |= (*down as u16) << index;
+        }
+        out.extend_from_slice(&keys.to_le_bytes());
+        out.push(self.wait_key.unwrap_or(0xFF));
+        out.push(self.sprite_drawn as u8);
+        out.push(match self.mode {
+            Chip8Mode::Running => 0,
+            Chip8Mode::WaitingKey => 1,
+            Chip8Mode::Stopped => 2,
+        });
+        let (kind, state) = match self.rng {
+            Rng::Thread | Rng::External(_) => (0, 0),
+            Rng::Seeded(state) => (1, state),
+            Rng::Vip { pointer, seed } => (2, pointer as u64 | (seed as u64) << 8),
+        };
+        out.push(kind);
+        out.extend_from_slice(&state.to_le_bytes());
+        out.extend_from_slice(&self.cycles.to_le_bytes());
+        out.push(self.wait_reg);
+        out
+    }
+}
+
+// loading needs a placeholder for the storage while the state is read
+impl<M: Storage + Default> Chip8<M> {
+    // the emulator is left untouched if the state cannot be read
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let mut reader = StateReader { data, pos: 0 };
+        if reader.bytes(4)? != MAGIC {
+            return Err(Chip8ErrorKind::InvalidSaveState.into());
+        }
+        let version = reader.u16()?;
+        if version == 0 || version > STATE_VERSION {
+            return Err(Chip8ErrorKind::UnsupportedSaveStateVersion.into());
+        }
+
+        // the state takes over this emulator's storage once it has all been
+        // read, until then it has none
+        let mut state = Chip8::blank(M::default());
+        state.platform = match reader.u8()? {
+            0 => Platform::Chip8,
+            1 => Platform::SuperChip,
+            2 => Platform::XoChip,
+            _ => return Err(Chip8ErrorKind::InvalidSaveState.into()),
+        };
+        state.v.copy_from_slice(reader.bytes(0x10)?);
+        state.pc = reader.u16()?;
+        state.i = reader.u16()?;
+        for addr in state.stack.iter_mut() {
+            *addr = reader.u16()?;
+        }
+        state.stack_pos = reader.u8()?;
+        if state.stack_pos as usize > state.stack.len() {
+            return Err(Chip8ErrorKind::InvalidSaveState.into());
+        }
+        state.delay_timer = reader.u8()?;
+        state.sound_timer = reader.u8()?;
+        let size = state.platform.memory_size().min(self.memory.as_ref().len());
+        if reader.u32()? as usize != size {
+            return Err(Chip8ErrorKind::InvalidSaveState.into());
+        }
+        let memory = reader.bytes(size)?;
+        for row in state.pixels.iter_mut() {
+            row.copy_from_slice(reader.bytes(128)?);
+        }
+        state.planes = reader.u8()? & 0x3;
+        state.hires = reader.bool()?;
+        state.rpl.copy_from_slice(reader.bytes(0x10)?);
+        state.pattern.copy_from_slice(reader.bytes(16)?);
+        state.pitch = reader.u8()?;
+        let keys = reader.u16()?;
+        for (index, down) in state.down_keys.iter_mut().enumerate() {
+            *down = keys & (1 << index) != 0;
+        }
+        state.wait_key = match reader.u8()? {
+            0xFF => None,
+            key if key < 0x10 => Some(key),
+            _ => return Err(Chip8ErrorKind::InvalidSaveState.into()),
+        };
+        state.sprite_drawn = reader.bool()?;
+        state.mode = match reader.u8()? {
+            0 => Chip8Mode::Running,
+            1 => Chip8Mode::WaitingKey,
+            2 => Chip8Mode::Stopped,
+            _ => return Err(Chip8ErrorKind::InvalidSaveState.into()),
+        };
+        state.rng = if version >= 2 {
+            let kind = reader.u8()?;
+            let rng_state = reader.u64()?;
+            match kind {
+                // an external source has nothing to restore either
+                0 => match self.rng {
+                    Rng::External(_) => self.rng.clone(),
+                    _ => Rng::Thread,
+                },
+                1 => Rng::Seeded(rng_state),
+                2 => Rng::Vip {
+                    pointer: rng_state as u8,
+                    seed: (rng_state >> 8) as u8,
+                },
+                _ => return Err(Chip8ErrorKind::InvalidSaveState.into()),
+            }
+        } else {
+            self.rng.clone()
+        };
+        if version >= 3 {
+            state.cycles = reader.u64()?;
+        }
+        if version >= 4 {
+            state.wait_reg = reader.u8()? & 0xF;
+        } else {
+            state.wait_key = None;
+        }
+        if reader.pos != data.len() {
+            return Err(Chip8ErrorKind::InvalidSaveState.into());
+        }
+
+        state.memory = std::mem::take(&mut self.memory);
+        state.memory.as_mut().fill(0);
+        state.memory.as_mut()[..size].copy_from_slice(memory);
+
+        // quirks, the fault policy, addressing, where the font is, the movie
+        // hooks and tracing are configuration rather than machine state.
+        // Queued key events are dropped, their cycles belong to the run being
+        // left
+        state.quirks = self.quirks;
+        state.faults = self.faults;
+        state.addressing = self.addressing;
+        state.font_addr = self.font_addr;
+        state.big_glyphs = self.big_glyphs;
+        state.random_log = self.random_log.take();
+        state.random_replay = self.random_replay.take();
+        state.key_log = self.key_log.take();
+        state.tracer = self.tracer.take();
+        *self = state;
+        Ok(())
+    }
+}
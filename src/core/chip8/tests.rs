@@ -0,0 +1,215 @@
+// These run with and without std, so they only use memory they own.
+use super::{Chip8, Chip8Mode, Platform};
+use crate::core::error::{Chip8ErrorKind, FaultAction, FaultPolicy};
+use crate::core::font::Font;
+use crate::core::input::KeyEvent;
+use crate::core::rng::Rng;
+
+fn start<'a>(memory: &'a mut [u8], rom: &[u8]) -> Chip8<&'a mut [u8]> {
+    let mut emu = Chip8::with_memory(memory);
+    emu.load_rom_bytes(rom, 0x200).unwrap();
+    emu.mode = Chip8Mode::Running;
+    emu
+}
+
+fn run(emu: &mut Chip8<&mut [u8]>, cycles: usize) {
+    for _ in 0..cycles {
+        emu.clock().unwrap();
+    }
+}
+
+#[test]
+fn runs_from_a_caller_buffer() {
+    let mut memory = [0xAA; 0x1000];
+    // V1 = 5, V1 += 2, loop
+    let mut emu = start(&mut memory, &[0x61, 0x05, 0x71, 0x02, 0x12, 0x04]);
+    run(&mut emu, 3);
+    assert_eq!(emu.get_registers()[1], 7);
+    assert_eq!(emu.get_pc(), 0x204);
+    // the buffer is cleared apart from the font and the rom
+    assert_eq!(emu.get_memory()[0x300], 0);
+}
+
+#[test]
+fn refuses_a_small_buffer() {
+    let mut memory = [0; 0x800];
+    assert_eq!(
+        Chip8::try_with_memory(&mut memory[..]).err().unwrap().kind,
+        Chip8ErrorKind::MemoryTooSmall
+    );
+    let mut memory = [0xFF; 0x1000];
+    let emu = Chip8::try_with_memory(&mut memory[..]).ok().unwrap();
+    assert_eq!(emu.get_memory()[0x200], 0);
+}
+
+#[test]
+#[should_panic(expected = "memory needs at least 0x1000 bytes")]
+fn with_memory_panics_on_a_small_buffer() {
+    let mut memory = [0; 0x800];
+    Chip8::with_memory(&mut memory[..]);
+}
+
+#[test]
+fn xo_chip_memory_is_limited_by_the_storage() {
+    let mut memory = [0; 0x1000];
+    let mut emu = Chip8::with_memory(&mut memory[..]);
+    emu.set_platform(Platform::XoChip);
+    assert_eq!(emu.get_memory().len(), 0x1000);
+    assert_eq!(
+        emu.load_rom_bytes(&[0; 0x20], 0xFF0).unwrap_err().kind,
+        Chip8ErrorKind::RomTooLarge
+    );
+}
+
+#[test]
+fn load_address_is_the_entry_point() {
+    let mut memory = [0; 0x1000];
+    let mut emu = Chip8::with_memory(&mut memory[..]);
+    emu.load_rom_bytes(&[0x60, 0x01], 0x600).unwrap();
+    assert_eq!(emu.get_pc(), 0x600);
+    assert_eq!(
+        emu.load_rom_bytes(&[], 0x200).unwrap_err().kind,
+        Chip8ErrorKind::EmptyRom
+    );
+}
+
+#[test]
+fn store_that_ends_at_the_last_byte_fits() {
+    let mut memory = [0; 0x1000];
+    // I = FF0, store V0-VF, load them back
+    let mut emu = start(&mut memory, &[0xAF, 0xF0, 0xFF, 0x55, 0xFF, 0x65]);
+    emu.set_register(0xF, 0x42);
+    run(&mut emu, 2);
+    assert_eq!(emu.get_memory()[0xFFF], 0x42);
+    assert_eq!(emu.get_index(), 0x1000);
+    // I now points past the end, so the load traps without touching V
+    emu.set_register(0, 0x99);
+    let error = emu.clock().unwrap_err();
+    assert_eq!(error.kind, Chip8ErrorKind::AddressOverflow);
+    assert_eq!(emu.get_registers()[0], 0x99);
+}
+
+#[test]
+fn add_to_index_wraps_the_register() {
+    let mut memory = [0; 0x1000];
+    // I = FFF, V0 = FF, I += V0
+    let mut emu = start(&mut memory, &[0xAF, 0xFF, 0x60, 0xFF, 0xF0, 0x1E]);
+    run(&mut emu, 3);
+    assert_eq!(emu.get_index(), 0x10FE);
+}
+
+#[test]
+fn seeded_rng_repeats() {
+    let rom = [0xC0, 0xFF, 0xC1, 0xFF];
+    let mut first = [0; 0x1000];
+    let mut second = [0; 0x1000];
+    let mut a = start(&mut first, &rom);
+    let mut b = start(&mut second, &rom);
+    a.set_rng(Rng::seeded(7));
+    b.set_rng(Rng::seeded(7));
+    run(&mut a, 2);
+    run(&mut b, 2);
+    assert_eq!(a.get_registers()[..2], b.get_registers()[..2]);
+}
+
+#[test]
+fn key_wait_finishes_on_release() {
+    let mut memory = [0; 0x1000];
+    // V2 = key, loop
+    let mut emu = start(&mut memory, &[0xF2, 0x0A, 0x12, 0x02]);
+    run(&mut emu, 1);
+    assert_eq!(emu.mode, Chip8Mode::WaitingKey);
+    emu.key_down(5);
+    run(&mut emu, 3);
+    assert_eq!(emu.mode, Chip8Mode::WaitingKey);
+    emu.key_up(5);
+    assert_eq!(emu.mode, Chip8Mode::Running);
+    assert_eq!(emu.get_registers()[2], 5);
+}
+
+#[test]
+fn queued_keys_apply_at_their_cycle() {
+    let mut memory = [0; 0x1000];
+    // loop on the spot
+    let mut emu = start(&mut memory, &[0x12, 0x00]);
+    emu.queue_key(KeyEvent {
+        cycle: 3,
+        key: 0xA,
+        down: true,
+    });
+    run(&mut emu, 3);
+    assert!(!emu.is_key_down(0xA));
+    run(&mut emu, 1);
+    assert!(emu.is_key_down(0xA));
+}
+
+#[test]
+fn font_without_big_glyphs_is_refused() {
+    let mut memory = [0; 0x1000];
+    let mut emu = Chip8::with_memory(&mut memory[..]);
+    for big_glyphs in [0, 17] {
+        let font = Font {
+            big_glyphs,
+            ..Font::SCHIP
+        };
+        assert_eq!(
+            emu.set_font(&font, 0x50).unwrap_err().kind,
+            Chip8ErrorKind::InvalidFont
+        );
+    }
+    // the font in use is left alone
+    assert_eq!(emu.get_font_address(), 0);
+    emu.set_font(&Font::OCTO, 0x50).unwrap();
+    assert_eq!(emu.get_font_address(), 0x50);
+}
+
+#[test]
+fn ignored_fetch_fault_moves_on() {
+    let mut memory = [0; 0x1000];
+    // jump to the last byte, where the instruction runs off the end
+    let mut emu = start(&mut memory, &[0x1F, 0xFF]);
+    emu.faults = FaultPolicy::all(FaultAction::Ignore);
+    run(&mut emu, 3);
+    assert_eq!(emu.get_cycles(), 3);
+    assert_eq!(emu.get_pc(), 0x1003);
+    assert_eq!(emu.mode, Chip8Mode::Running);
+}
+
+#[test]
+fn halted_fetch_fault_stays_put() {
+    let mut memory = [0; 0x1000];
+    let mut emu = start(&mut memory, &[0x1F, 0xFF]);
+    run(&mut emu, 1);
+    let error = emu.clock().unwrap_err();
+    assert_eq!(error.kind, Chip8ErrorKind::AddressOverflow);
+    assert_eq!(error.pc, Some(0xFFF));
+    assert_eq!(emu.mode, Chip8Mode::Stopped);
+}
+
+#[test]
+fn platform_font_moves_back_when_it_does_not_fit() {
+    let mut memory = [0; 0x1000];
+    let mut emu = Chip8::with_memory(&mut memory[..]);
+    // the VIP font ends at the last byte, the bigger XO-CHIP one can't
+    emu.set_font(&Font::VIP, 0xF4C).unwrap();
+    emu.set_platform(Platform::XoChip);
+    assert_eq!(emu.get_font_address(), 0);
+    assert_eq!(emu.get_memory()[..80], Font::OCTO.small);
+}
+
+#[test]
+fn platform_font_stays_inside_smaller_memory() {
+    let mut memory = [0; 0x10000];
+    let mut emu = Chip8::with_memory(&mut memory[..]);
+    emu.set_platform(Platform::XoChip);
+    emu.set_font(&Font::OCTO, 0xFF00).unwrap();
+    emu.set_platform(Platform::Chip8);
+    assert_eq!(emu.get_font_address(), 0);
+    // V0 = 0, I = glyph 0, draw it
+    emu.load_rom_bytes(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05], 0x200)
+        .unwrap();
+    emu.mode = Chip8Mode::Running;
+    run(&mut emu, 3);
+    assert_eq!(emu.get_index(), 0);
+    assert_ne!(emu.get_pixels()[0][0], 0);
+}
@@ -0,0 +1,83 @@
+// Trace lines hold the machine state before each instruction runs, written
+// in the KEY:VALUE style other emulators use for their logs so two traces
+// can be compared line by line:
+//
+//   CYC:0000000012 PC:0208 OP:D015 V0:00 V1:00 ... VF:00 I:0000 SP:00 DT:00 ST:00 ; draw v0, v1, 5
+//
+// CYC counts the instructions run before this one and SP is the stack
+// depth. The mnemonic after the ; can be cut off when diffing against an
+// emulator that doesn't print one. XO-CHIP's F000 NNNN only shows its first
+// word in OP.
+use super::{Chip8, Storage};
+use crate::core::disasm::disassemble;
+use std::io::Write;
+
+pub struct Tracer {
+    out: Box<dyn Write>,
+    // only instructions at addresses in this inclusive range are written
+    range: Option<(u16, u16)>,
+    // bit n set traces opcodes whose top nibble is n
+    classes: u16,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>) -> Tracer {
+        Tracer {
+            out,
+            range: None,
+            classes: 0xFFFF,
+        }
+    }
+
+    pub fn set_range(&mut self, start: u16, end: u16) {
+        self.range = Some((start, end));
+    }
+
+    pub fn set_classes(&mut self, classes: u16) {
+        self.classes = classes;
+    }
+
+    fn wants(&self, pc: u16, instr: u16) -> bool {
+        let in_range = self
+            .range
+            .is_none_or(|(start, end)| pc >= start && pc <= end);
+        in_range && self.classes & (1 << (instr >> 12)) != 0
+    }
+}
+
+impl<M: Storage> Chip8<M> {
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    // the trace line for the instruction at pc, before it runs
+    pub fn trace_line(&self) -> String {
+        let byte = |addr: u16| self.get_memory().get(addr as usize).copied().unwrap_or(0);
+        let instr = (byte(self.pc) as u16) << 8 | byte(self.pc.wrapping_add(1)) as u16;
+
+        let mut line = format!(
+            "CYC:{:010} PC:{:04X} OP:{:04X}",
+            self.cycles, self.pc, instr
+        );
+        for (index, value) in self.v.iter().enumerate() {
+            line.push_str(&format!(" V{:X}:{:02X}", index, value));
+        }
+        let (text, _) = disassemble(self.get_memory(), self.pc as usize, self.platform);
+        line.push_str(&format!(
+            " I:{:04X} SP:{:02X} DT:{:02X} ST:{:02X} ; {}",
+            self.i, self.stack_pos, self.delay_timer, self.sound_timer, text
+        ));
+        line
+    }
+
+    pub(super) fn trace_instr(&mut self, pc: u16, instr: u16) {
+        if !self.tracer.as_ref().is_some_and(|t| t.wants(pc, instr)) {
+            return;
+        }
+
+        let line = self.trace_line();
+        if let Some(tracer) = &mut self.tracer {
+            let _ = writeln!(tracer.out, "{}", line);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::core::Platform;
+
+// decodes the instruction at addr, returning the text and the instruction
+// length in bytes
+pub fn disassemble(memory: &[u8], addr: usize, platform: Platform) -> (String, usize) {
+    let byte = |offset: usize| memory.get(addr + offset).copied().unwrap_or(0);
+    let instr = (byte(0) as u16) << 8 | byte(1) as u16;
+
+    if instr == 0xF000 && platform == Platform::XoChip {
+        let operand = (byte(2) as u16) << 8 | byte(3) as u16;
+        return (format!("movil {:#06X}", operand), 4);
+    }
+
+    let text = match decode(instr, platform, &|addr| format!("{:#05X}", addr)) {
+        Some(text) => text,
+        None => format!(".byte {:#04X}, {:#04X}", byte(0), byte(1)),
+    };
+    (text, 2)
+}
+
+// the chipc mnemonic for a two byte instruction, or None if it isn't one on
+// platform. chipc only knows the CHIP-8 set, so only Platform::Chip8 output
+// assembles, the SCHIP and XO-CHIP names are for reading. target names the
+// address of a jump, call or movi, the disassembler uses it for labels
+pub fn decode(instr: u16, platform: Platform, target: &dyn Fn(u16) -> String) -> Option<String> {
+    let x = (instr & 0x0F00) >> 8;
+    let y = (instr & 0x00F0) >> 4;
+    let addr = instr & 0x0FFF;
+    let imm_8 = instr & 0x00FF;
+    let imm_4 = instr & 0x000F;
+    let schip = platform != Platform::Chip8;
+    let xo = platform == Platform::XoChip;
+
+    let text = match (instr >> 12, imm_8) {
+        (0x0, 0xE0) if x == 0 => "clr".to_string(),
+        (0x0, 0xEE) if x == 0 => "ret".to_string(),
+        (0x0, _) if schip && x == 0 && y == 0xC => format!("scd {}", imm_4),
+        (0x0, _) if xo && x == 0 && y == 0xD => format!("scu {}", imm_4),
+        (0x0, 0xFB) if schip && x == 0 => "scr".to_string(),
+        (0x0, 0xFC) if schip && x == 0 => "scl".to_string(),
+        (0x0, 0xFD) if schip && x == 0 => "exit".to_string(),
+        (0x0, 0xFE) if schip && x == 0 => "low".to_string(),
+        (0x0, 0xFF) if schip && x == 0 => "high".to_string(),
+        (0x0, _) => format!("sys {:#05X}", addr),
+        (0x1, _) => format!("j {}", target(addr)),
+        (0x2, _) => format!("call {}", target(addr)),
+        (0x3, _) => format!("be v{:X}, {:#04X}", x, imm_8),
+        (0x4, _) => format!("bne v{:X}, {:#04X}", x, imm_8),
+        (0x5, _) if imm_4 == 0x0 => format!("be v{:X}, v{:X}", x, y),
+        (0x5, _) if xo && imm_4 == 0x2 => format!("save v{:X}, v{:X}", x, y),
+        (0x5, _) if xo && imm_4 == 0x3 => format!("load v{:X}, v{:X}", x, y),
+        (0x6, _) => format!("mov v{:X}, {:#04X}", x, imm_8),
+        (0x7, _) => format!("add v{:X}, {:#04X}", x, imm_8),
+        (0x8, _) => {
+            let mnemonic = match imm_4 {
+                0x0 => "mov",
+                0x1 => "or",
+                0x2 => "and",
+                0x3 => "xor",
+                0x4 => "add",
+                0x5 => "sub",
+                0x6 => "sr",
+                0x7 => "subn",
+                0xE => "sl",
+                _ => return None,
+            };
+            format!("{} v{:X}, v{:X}", mnemonic, x, y)
+        }
+        (0x9, _) if imm_4 == 0x0 => format!("bne v{:X}, v{:X}", x, y),
+        (0xA, _) => format!("movi {}", target(addr)),
+        (0xB, _) => format!("jr {}", target(addr)),
+        (0xC, _) => format!("rand v{:X}, {:#04X}", x, imm_8),
+        (0xD, _) => format!("draw v{:X}, v{:X}, {}", x, y, imm_4),
+        (0xE, 0x9E) => format!("bku v{:X}", x),
+        (0xE, 0xA1) => format!("bkd v{:X}", x),
+        (0xF, 0x01) if xo => format!("plane {}", x),
+        (0xF, 0x02) if xo && x == 0 => "audio".to_string(),
+        (0xF, 0x07) => format!("gdt v{:X}", x),
+        (0xF, 0x0A) => format!("gkd v{:X}", x),
+        (0xF, 0x15) => format!("sdt v{:X}", x),
+        (0xF, 0x18) => format!("sst v{:X}", x),
+        (0xF, 0x1E) => format!("addi v{:X}", x),
+        (0xF, 0x29) => format!("gca v{:X}", x),
+        (0xF, 0x30) if schip => format!("gbca v{:X}", x),
+        (0xF, 0x33) => format!("sbcd v{:X}", x),
+        (0xF, 0x3A) if xo => format!("pitch v{:X}", x),
+        (0xF, 0x55) => format!("sb v{:X}", x),
+        (0xF, 0x65) => format!("lb v{:X}", x),
+        (0xF, 0x75) if schip => format!("srpl v{:X}", x),
+        (0xF, 0x85) if schip => format!("lrpl v{:X}", x),
+        _ => return None,
+    };
+    Some(text)
+}
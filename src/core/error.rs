@@ -0,0 +1,162 @@
+use ::core::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Chip8ErrorKind {
+    InvalidInstruction,
+    StackOverflow,
+    StackUnderflow,
+    AddressOverflow,
+    BadRomPath,
+    IOError,
+    BadArchive,
+    EmptyRom,
+    RomTooLarge,
+    InvalidSaveState,
+    UnsupportedSaveStateVersion,
+    InvalidFont,
+    MemoryTooSmall,
+}
+
+impl Chip8ErrorKind {
+    fn description(&self) -> &'static str {
+        match self {
+            Chip8ErrorKind::InvalidInstruction => "invalid instruction",
+            Chip8ErrorKind::StackOverflow => "stack overflow",
+            Chip8ErrorKind::StackUnderflow => "return with an empty stack",
+            Chip8ErrorKind::AddressOverflow => "address out of range",
+            Chip8ErrorKind::BadRomPath => "could not open rom",
+            Chip8ErrorKind::IOError => "could not read rom",
+            Chip8ErrorKind::BadArchive => "could not unpack rom archive",
+            Chip8ErrorKind::EmptyRom => "rom is empty",
+            Chip8ErrorKind::RomTooLarge => "rom does not fit in memory",
+            Chip8ErrorKind::InvalidSaveState => "invalid save state",
+            Chip8ErrorKind::UnsupportedSaveStateVersion => "unsupported save state version",
+            Chip8ErrorKind::InvalidFont => "font needs 1 to 16 big glyphs",
+            Chip8ErrorKind::MemoryTooSmall => "memory needs at least 0x1000 bytes",
+        }
+    }
+}
+
+// the context is filled in by clock for errors raised running an
+// instruction, the rest only have a kind
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Chip8Error {
+    pub kind: Chip8ErrorKind,
+    // address and first word of the instruction that failed
+    pub pc: Option<u16>,
+    pub opcode: Option<u16>,
+    // return addresses on the stack when it failed
+    pub stack_depth: Option<u8>,
+    // for address faults, the first address out of range
+    pub address: Option<u32>,
+    // why reading a rom failed, when the os said
+    #[cfg(feature = "std")]
+    pub io: Option<std::io::ErrorKind>,
+}
+
+impl Chip8Error {
+    pub fn address_overflow(address: usize) -> Chip8Error {
+        Chip8Error {
+            address: Some(address as u32),
+            ..Chip8ErrorKind::AddressOverflow.into()
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn io(kind: Chip8ErrorKind, error: &std::io::Error) -> Chip8Error {
+        Chip8Error {
+            io: Some(error.kind()),
+            ..kind.into()
+        }
+    }
+}
+
+impl From<Chip8ErrorKind> for Chip8Error {
+    fn from(kind: Chip8ErrorKind) -> Chip8Error {
+        Chip8Error {
+            kind,
+            pc: None,
+            opcode: None,
+            stack_depth: None,
+            address: None,
+            #[cfg(feature = "std")]
+            io: None,
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind.description())?;
+        if let Some(address) = self.address {
+            write!(f, " {:#06X}", address)?;
+        }
+        if let Some(pc) = self.pc {
+            write!(f, " at {:#06X}", pc)?;
+        }
+        if let Some(opcode) = self.opcode {
+            write!(f, " running {:04X}", opcode)?;
+        }
+        if let Some(depth) = self.stack_depth {
+            write!(f, ", stack depth {}", depth)?;
+        }
+        #[cfg(feature = "std")]
+        if let Some(io) = self.io {
+            write!(f, ": {}", io)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Chip8Error {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FaultAction {
+    // stop the emulator and return the error
+    Halt,
+    // carry on with the next instruction as if nothing happened
+    Ignore,
+    // return the error with pc left on the failed instruction, so a
+    // debugger can look at it and resume
+    Pause,
+}
+
+// what clock does with each kind of error an instruction can raise
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FaultPolicy {
+    pub invalid_instruction: FaultAction,
+    pub stack_overflow: FaultAction,
+    pub stack_underflow: FaultAction,
+    pub address_overflow: FaultAction,
+}
+
+impl FaultPolicy {
+    pub const HALT: FaultPolicy = FaultPolicy::all(FaultAction::Halt);
+
+    pub const fn all(action: FaultAction) -> FaultPolicy {
+        FaultPolicy {
+            invalid_instruction: action,
+            stack_overflow: action,
+            stack_underflow: action,
+            address_overflow: action,
+        }
+    }
+
+    // errors that can't come from running an instruction always halt
+    pub fn action(&self, kind: Chip8ErrorKind) -> FaultAction {
+        match kind {
+            Chip8ErrorKind::InvalidInstruction => self.invalid_instruction,
+            Chip8ErrorKind::StackOverflow => self.stack_overflow,
+            Chip8ErrorKind::StackUnderflow => self.stack_underflow,
+            Chip8ErrorKind::AddressOverflow => self.address_overflow,
+            _ => FaultAction::Halt,
+        }
+    }
+}
+
+impl Default for FaultPolicy {
+    fn default() -> FaultPolicy {
+        FaultPolicy::HALT
+    }
+}
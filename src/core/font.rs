@@ -0,0 +1,191 @@
+// Each font has 16 small 5 byte glyphs for FX29 and big 10 byte glyphs for
+// FX30. The big ones go right after the small ones in memory. Interpreters
+// without a big font of their own get the SCHIP one.
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Font {
+    pub small: [u8; 80],
+    // only the first big_glyphs * 10 bytes are used
+    pub big: [u8; 160],
+    // 10 for digits only, 16 with A-F as well
+    pub big_glyphs: u8,
+}
+
+// space for big fonts with only the digits
+const fn digits_only(digits: [u8; 100]) -> [u8; 160] {
+    let mut big = [0; 160];
+    let mut index = 0;
+    while index < digits.len() {
+        big[index] = digits[index];
+        index += 1;
+    }
+    big
+}
+
+const SCHIP_BIG: [u8; 160] = digits_only([
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+]);
+
+impl Font {
+    pub const VIP: Font = Font {
+        small: [
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+            0x60, 0x20, 0x20, 0x20, 0x70, // 1
+            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+            0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+            0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+            0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+            0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+        ],
+        big: SCHIP_BIG,
+        big_glyphs: 10,
+    };
+
+    pub const DREAM6800: Font = Font {
+        small: [
+            0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+            0x40, 0x40, 0x40, 0x40, 0x40, // 1
+            0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+            0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+            0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+            0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+            0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+            0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+            0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+            0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+            0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+            0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+            0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+            0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+            0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+            0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+        ],
+        big: SCHIP_BIG,
+        big_glyphs: 10,
+    };
+
+    pub const ETI660: Font = Font {
+        small: [
+            0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+            0x20, 0x20, 0x20, 0x20, 0x20, // 1
+            0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+            0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+            0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+            0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+            0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+            0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+            0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+            0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+            0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+            0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+            0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+            0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+            0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+            0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+        ],
+        big: SCHIP_BIG,
+        big_glyphs: 10,
+    };
+
+    pub const SCHIP: Font = Font {
+        small: [
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+            0x20, 0x60, 0x20, 0x20, 0x70, // 1
+            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+        ],
+        big: SCHIP_BIG,
+        big_glyphs: 10,
+    };
+
+    // the same small font as SCHIP, with big glyphs for A-F too
+    pub const OCTO: Font = Font {
+        small: Font::SCHIP.small,
+        big: [
+            0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+            0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+            0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+            0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+            0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+        ],
+        big_glyphs: 16,
+    };
+
+    pub const NAMES: [&'static str; 5] = ["vip", "dream6800", "eti660", "schip", "octo"];
+
+    pub fn from_name(name: &str) -> Option<Font> {
+        match name {
+            "vip" => Some(Font::VIP),
+            "dream6800" => Some(Font::DREAM6800),
+            "eti660" => Some(Font::ETI660),
+            "schip" => Some(Font::SCHIP),
+            "octo" => Some(Font::OCTO),
+            _ => None,
+        }
+    }
+
+    // a font file holds the 80 bytes of small glyphs, optionally followed by
+    // 10 or 16 big ones. Without big glyphs the SCHIP ones are used
+    pub fn from_bytes(data: &[u8]) -> Option<Font> {
+        let big_glyphs = match data.len() {
+            80 => 0,
+            180 => 10,
+            240 => 16,
+            _ => return None,
+        };
+        let mut font = Font::SCHIP;
+        font.small.copy_from_slice(&data[..80]);
+        if big_glyphs > 0 {
+            font.big = [0; 160];
+            font.big[..big_glyphs * 10].copy_from_slice(&data[80..]);
+            font.big_glyphs = big_glyphs as u8;
+        }
+        Some(font)
+    }
+
+    // bytes taken up in memory by the small and big glyphs
+    pub fn size(&self) -> usize {
+        80 + self.big_glyphs as usize * 10
+    }
+}
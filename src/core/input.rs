@@ -0,0 +1,77 @@
+// Key events are queued with the cycle they belong to and applied by clock
+// just before the instruction that starts at that cycle, so presses shorter
+// than a frame keep their place within it. The queue is a fixed ring buffer
+// since the core can't allocate without std.
+
+const QUEUE_SIZE: usize = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KeyEvent {
+    // applied once this many instructions have run
+    pub cycle: u64,
+    pub key: u8,
+    pub down: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct InputQueue {
+    events: [KeyEvent; QUEUE_SIZE],
+    start: usize,
+    len: usize,
+}
+
+impl InputQueue {
+    pub fn new() -> InputQueue {
+        InputQueue {
+            events: [KeyEvent {
+                cycle: 0,
+                key: 0,
+                down: false,
+            }; QUEUE_SIZE],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    fn last(&self) -> Option<&KeyEvent> {
+        match self.len {
+            0 => None,
+            len => Some(&self.events[(self.start + len - 1) % QUEUE_SIZE]),
+        }
+    }
+
+    // events stay in the order they were pushed, one stamped earlier than
+    // the event before it happens at the same cycle. A full queue gives back
+    // its oldest event to make room
+    pub fn push(&mut self, mut event: KeyEvent) -> Option<KeyEvent> {
+        if let Some(last) = self.last() {
+            event.cycle = event.cycle.max(last.cycle);
+        }
+        let dropped = if self.len == QUEUE_SIZE {
+            self.pop()
+        } else {
+            None
+        };
+        self.events[(self.start + self.len) % QUEUE_SIZE] = event;
+        self.len += 1;
+        dropped
+    }
+
+    fn pop(&mut self) -> Option<KeyEvent> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.events[self.start];
+        self.start = (self.start + 1) % QUEUE_SIZE;
+        self.len -= 1;
+        Some(event)
+    }
+
+    // the next event due by cycle
+    pub fn pop_due(&mut self, cycle: u64) -> Option<KeyEvent> {
+        match self.events[self.start] {
+            event if self.len > 0 && event.cycle <= cycle => self.pop(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// how FX55/FX65 leave the index register once they are done
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IndexIncrement {
+    // i += x + 1
+    XPlusOne,
+    // i += x
+    X,
+    Unchanged,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quirks {
+    // 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub vf_reset: bool,
+    // 8XY6 and 8XYE shift Vx in place instead of loading Vy into Vx
+    pub shift_vx: bool,
+    pub index_increment: IndexIncrement,
+    // DXYN waits for the start of the next frame before drawing
+    pub display_wait: bool,
+    // sprites are cut off at the screen edges instead of wrapping around
+    pub clip_sprites: bool,
+    // BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_vx: bool,
+    // FX0A finishes as soon as a key goes down instead of when it is released
+    pub key_wait_press: bool,
+}
+
+impl Quirks {
+    pub const VIP: Quirks = Quirks {
+        vf_reset: true,
+        shift_vx: false,
+        index_increment: IndexIncrement::XPlusOne,
+        display_wait: true,
+        clip_sprites: true,
+        jump_vx: false,
+        key_wait_press: false,
+    };
+
+    pub const CHIP48: Quirks = Quirks {
+        vf_reset: false,
+        shift_vx: true,
+        index_increment: IndexIncrement::X,
+        display_wait: false,
+        clip_sprites: true,
+        jump_vx: true,
+        key_wait_press: false,
+    };
+
+    pub const SCHIP_LEGACY: Quirks = Quirks {
+        vf_reset: false,
+        shift_vx: true,
+        index_increment: IndexIncrement::Unchanged,
+        display_wait: true,
+        clip_sprites: true,
+        jump_vx: true,
+        key_wait_press: false,
+    };
+
+    pub const SCHIP_MODERN: Quirks = Quirks {
+        vf_reset: false,
+        shift_vx: true,
+        index_increment: IndexIncrement::Unchanged,
+        display_wait: false,
+        clip_sprites: true,
+        jump_vx: true,
+        key_wait_press: false,
+    };
+
+    pub const XO_CHIP: Quirks = Quirks {
+        vf_reset: false,
+        shift_vx: false,
+        index_increment: IndexIncrement::XPlusOne,
+        display_wait: false,
+        clip_sprites: false,
+        jump_vx: false,
+        key_wait_press: false,
+    };
+
+    pub const PRESET_NAMES: [&'static str; 5] =
+        ["vip", "chip48", "schip-legacy", "schip-modern", "xo-chip"];
+
+    pub fn from_preset(name: &str) -> Option<Quirks> {
+        match name {
+            "vip" => Some(Quirks::VIP),
+            "chip48" => Some(Quirks::CHIP48),
+            "schip-legacy" => Some(Quirks::SCHIP_LEGACY),
+            "schip-modern" => Some(Quirks::SCHIP_MODERN),
+            "xo-chip" => Some(Quirks::XO_CHIP),
+            _ => None,
+        }
+    }
+
+    // a preset name, or a comma separated list of quirks to flip with the
+    // index increment given as index=x+1, index=x or index=none
+    #[cfg(feature = "std")]
+    pub fn changed(self, changes: &str) -> Result<Quirks, String> {
+        if let Some(preset) = Quirks::from_preset(changes) {
+            return Ok(preset);
+        }
+
+        let mut quirks = self;
+        for change in changes.split(',') {
+            match change.trim() {
+                "vf_reset" => quirks.vf_reset = !quirks.vf_reset,
+                "shift_vx" => quirks.shift_vx = !quirks.shift_vx,
+                "display_wait" => quirks.display_wait = !quirks.display_wait,
+                "clip_sprites" => quirks.clip_sprites = !quirks.clip_sprites,
+                "jump_vx" => quirks.jump_vx = !quirks.jump_vx,
+                "key_wait_press" => quirks.key_wait_press = !quirks.key_wait_press,
+                "index=x+1" => quirks.index_increment = IndexIncrement::XPlusOne,
+                "index=x" => quirks.index_increment = IndexIncrement::X,
+                "index=none" => quirks.index_increment = IndexIncrement::Unchanged,
+                other => return Err(format!("unknown quirk {}", other)),
+            }
+        }
+        Ok(quirks)
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::VIP
+    }
+}
@@ -0,0 +1,67 @@
+#[cfg(feature = "std")]
+use rand::random;
+
+// source of the random bytes used by CXNN
+#[derive(Debug, Clone)]
+pub enum Rng {
+    // thread random, not reproducible and has no state to save
+    #[cfg(feature = "std")]
+    Thread,
+    // a host supplied source such as a hardware rng, not saved either
+    External(fn() -> u8),
+    // splitmix64, the value is the whole generator state
+    Seeded(u64),
+    // The COSMAC VIP interpreter steps RB.0 through its own code page at
+    // 0x0100 and adds the byte found there to RB.1, which is the result.
+    // The sequence only matches real hardware if that page of memory holds
+    // the VIP interpreter.
+    Vip {
+        pointer: u8,
+        seed: u8,
+    },
+}
+
+// without std there is no thread rng, so builds start from a fixed seed
+// unless given something else
+impl Default for Rng {
+    #[cfg(feature = "std")]
+    fn default() -> Rng {
+        Rng::Thread
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn default() -> Rng {
+        Rng::Seeded(0)
+    }
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Rng {
+        Rng::Seeded(seed)
+    }
+
+    pub fn vip(seed: u8) -> Rng {
+        Rng::Vip { pointer: 0, seed }
+    }
+
+    pub fn next_u8(&mut self, memory: &[u8]) -> u8 {
+        match self {
+            #[cfg(feature = "std")]
+            Rng::Thread => random::<u8>(),
+            Rng::External(source) => source(),
+            Rng::Seeded(state) => {
+                *state = state.wrapping_add(0x9E3779B97F4A7C15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+                z ^= z >> 31;
+                (z >> 56) as u8
+            }
+            Rng::Vip { pointer, seed } => {
+                *pointer = pointer.wrapping_add(1);
+                *seed = seed.wrapping_add(memory[0x100 + *pointer as usize]);
+                *seed
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// Roms can come from a file, stdin when the path is "-", or inside a .zip or
+// .gz archive. A zip with several files gives its first rom by extension, or
+// its first file if none of them look like a rom.
+use crate::core::chip8::MAX_MEMORY;
+use crate::core::error::{Chip8Error, Chip8ErrorKind};
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+const ROM_EXTENSIONS: [&str; 4] = [".ch8", ".c8", ".sc8", ".xo8"];
+
+pub fn read_rom(path: &str) -> Result<Vec<u8>, Chip8Error> {
+    if path == "-" {
+        return read_capped(io::stdin(), Chip8ErrorKind::IOError);
+    }
+
+    let mut file = File::open(path).map_err(|e| Chip8Error::io(Chip8ErrorKind::BadRomPath, &e))?;
+    let lower = path.to_lowercase();
+    if !lower.ends_with(".gz") && !lower.ends_with(".zip") {
+        return read_capped(file, Chip8ErrorKind::IOError);
+    }
+
+    // archives can hold more than one rom, only what comes out is capped
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|e| Chip8Error::io(Chip8ErrorKind::IOError, &e))?;
+    if lower.ends_with(".gz") {
+        read_capped(GzDecoder::new(&data[..]), Chip8ErrorKind::BadArchive)
+    } else {
+        read_zip(data)
+    }
+}
+
+// stops a byte past the most memory any platform has, so an endless stream
+// or an archive bomb is too large rather than filling up the host
+fn read_capped(reader: impl Read, error: Chip8ErrorKind) -> Result<Vec<u8>, Chip8Error> {
+    let mut rom = Vec::new();
+    reader
+        .take(MAX_MEMORY as u64 + 1)
+        .read_to_end(&mut rom)
+        .map_err(|e| Chip8Error::io(error, &e))?;
+    if rom.len() > MAX_MEMORY {
+        return Err(Chip8ErrorKind::RomTooLarge.into());
+    }
+    Ok(rom)
+}
+
+fn read_zip(data: Vec<u8>) -> Result<Vec<u8>, Chip8Error> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(bad_zip)?;
+    let mut first = None;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index).map_err(bad_zip)?;
+        if !file.is_file() {
+            continue;
+        }
+        let name = file.name().to_lowercase();
+        if ROM_EXTENSIONS.iter().any(|ext| name.ends_with(ext)) {
+            first = Some(index);
+            break;
+        }
+        first = first.or(Some(index));
+    }
+
+    let index = first.ok_or(Chip8ErrorKind::BadArchive)?;
+    let file = archive.by_index(index).map_err(bad_zip)?;
+    read_capped(file, Chip8ErrorKind::BadArchive)
+}
+
+// only keeps a reason when the zip failed reading, not when it was malformed
+fn bad_zip(error: ZipError) -> Chip8Error {
+    match error {
+        ZipError::Io(e) => Chip8Error::io(Chip8ErrorKind::BadArchive, &e),
+        _ => Chip8ErrorKind::BadArchive.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_rom;
+    use crate::core::chip8::MAX_MEMORY;
+    use crate::core::error::Chip8ErrorKind;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::{env, fs, process};
+
+    fn write_gz(name: &str, len: usize) -> String {
+        let path = env::temp_dir().join(format!("{}-{}.ch8.gz", name, process::id()));
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0; len]).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn gzip_stream_is_capped() {
+        // a few hundred bytes that would unpack to far more than any rom
+        let bomb = write_gz("bomb", MAX_MEMORY * 64);
+        let error = read_rom(&bomb).unwrap_err();
+        fs::remove_file(&bomb).unwrap();
+        assert_eq!(error.kind, Chip8ErrorKind::RomTooLarge);
+
+        let largest = write_gz("largest", MAX_MEMORY);
+        let rom = read_rom(&largest);
+        fs::remove_file(&largest).unwrap();
+        assert_eq!(rom.unwrap().len(), MAX_MEMORY);
+    }
+}
@@ -0,0 +1,10 @@
+// The emulator core and the chipc assembler, shared by the chip8emu and
+// chipc binaries and usable from other crates. Without the std feature only
+// the core is built, as no_std, leaving out file loading, the thread rng,
+// save states, tracing and the assembler. The default cli feature adds what
+// the binaries need on top of std.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod asm;
+pub mod core;